@@ -0,0 +1,435 @@
+use crate::{insert_hashed, shard_index, HashMap, PoisonPolicy, ShardMap};
+use std::collections::BTreeMap;
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+
+const MAGIC: [u8; 4] = *b"SHMP";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 4 + 4 + 8 + 8 + 8;
+
+/// Binary encoding of keys and values for [`ShardMap::write_to`] and
+/// [`ShardMap::read_from`].
+///
+/// `decode` consumes its bytes from the front of `buf`.
+pub trait Codec: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    fn decode(buf: &mut &[u8]) -> io::Result<Self>;
+}
+
+pub(crate) fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn take<'a>(buf: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if buf.len() < n {
+        return Err(invalid("unexpected end of shard section"));
+    }
+    let (head, tail) = buf.split_at(n);
+    *buf = tail;
+    Ok(head)
+}
+
+macro_rules! impl_codec_for_num {
+    ($($t:ty),*) => {$(
+        impl Codec for $t {
+            fn encode(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+                let bytes = take(buf, std::mem::size_of::<Self>())?;
+                Ok(Self::from_le_bytes(bytes.try_into().unwrap()))
+            }
+        }
+    )*};
+}
+
+impl_codec_for_num!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+impl Codec for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u64).encode(out);
+    }
+
+    fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+        usize::try_from(u64::decode(buf)?).map_err(|_| invalid("usize out of range"))
+    }
+}
+
+impl Codec for isize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as i64).encode(out);
+    }
+
+    fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+        isize::try_from(i64::decode(buf)?).map_err(|_| invalid("isize out of range"))
+    }
+}
+
+impl Codec for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+        match u8::decode(buf)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid("invalid bool")),
+        }
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self);
+    }
+
+    fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+        let len = usize::decode(buf)?;
+        Ok(take(buf, len)?.to_vec())
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(buf: &mut &[u8]) -> io::Result<Self> {
+        String::from_utf8(Vec::decode(buf)?).map_err(|_| invalid("invalid utf-8"))
+    }
+}
+
+// FNV-1a; guards against truncation and bit rot, not tampering.
+pub(crate) fn checksum(bytes: &[u8]) -> u64 {
+    extend_checksum(0xcbf2_9ce4_8422_2325, bytes)
+}
+
+fn extend_checksum(sum: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(sum, |h, &b| {
+        (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// covers the entry count and size in front of the payload as well, since
+// both are trusted to size the decoding.
+fn section_checksum(head: &[u8], payload: &[u8]) -> u64 {
+    extend_checksum(checksum(head), payload)
+}
+
+// identical only for hashers that place every key the same way, so a file
+// written with `RandomState` is always re-partitioned on load.
+fn hasher_id<S: BuildHasher>(hash_builder: &S) -> u64 {
+    hash_builder.hash_one(MAGIC)
+}
+
+pub(crate) struct Header {
+    pub(crate) shard_amount: usize,
+    pub(crate) hasher_id: u64,
+    pub(crate) len: usize,
+}
+
+impl Header {
+    pub(crate) fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut header = [0; HEADER_LEN + 8];
+        reader.read_exact(&mut header)?;
+        let mut buf = &header[..];
+        if take(&mut buf, 4)? != MAGIC {
+            return Err(invalid("not a shard map file"));
+        }
+        if u32::decode(&mut buf)? != VERSION {
+            return Err(invalid("unsupported shard map file version"));
+        }
+        let shard_amount = usize::decode(&mut buf)?;
+        let hasher_id = u64::decode(&mut buf)?;
+        let len = usize::decode(&mut buf)?;
+        if u64::decode(&mut buf)? != checksum(&header[..HEADER_LEN]) {
+            return Err(invalid("header checksum mismatch"));
+        }
+        if shard_amount == 0 {
+            return Err(invalid("shard amount must be greater than 0"));
+        }
+        Ok(Self {
+            shard_amount,
+            hasher_id,
+            len,
+        })
+    }
+
+    // with a matching hasher every entry must sit in the section of its own
+    // shard, so sections can be loaded independently.
+    pub(crate) fn direct<S: BuildHasher>(&self, hash_builder: &S) -> bool {
+        self.hasher_id == hasher_id(hash_builder)
+    }
+}
+
+pub(crate) fn misplaced() -> io::Error {
+    invalid("entry in the section of another shard")
+}
+
+pub(crate) fn count_mismatch() -> io::Error {
+    invalid("entry count mismatch")
+}
+
+pub(crate) struct Section {
+    pub(crate) len: usize,
+    payload: Vec<u8>,
+}
+
+impl Section {
+    pub(crate) fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut head = [0; 24];
+        let mut filled = 0;
+        while filled < head.len() {
+            match reader.read(&mut head[filled..]) {
+                // ending cleanly between sections means the header claims
+                // more shards than the file holds.
+                Ok(0) if filled == 0 => return Err(invalid("missing shard section")),
+                Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Ok(n) => filled += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let mut buf = &head[..];
+        let len = u64::decode(&mut buf)?;
+        let size = u64::decode(&mut buf)?;
+        let sum = u64::decode(&mut buf)?;
+        let mut payload = Vec::new();
+        reader.take(size).read_to_end(&mut payload)?;
+        if payload.len() as u64 != size {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if section_checksum(&head[..16], &payload) != sum {
+            return Err(invalid("shard section checksum mismatch"));
+        }
+        let len = usize::try_from(len).map_err(|_| invalid("shard section too long"))?;
+        Ok(Self { len, payload })
+    }
+
+    // bounds preallocation by the payload size, which was actually read.
+    pub(crate) fn capacity(&self) -> usize {
+        self.len.min(self.payload.len())
+    }
+
+    pub(crate) fn decode<K: Codec, V: Codec>(
+        &self,
+        mut f: impl FnMut(K, V) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut buf = self.payload.as_slice();
+        for _ in 0..self.len {
+            let k = K::decode(&mut buf)?;
+            let v = V::decode(&mut buf)?;
+            f(k, v)?;
+        }
+        if !buf.is_empty() {
+            return Err(invalid("trailing bytes in shard section"));
+        }
+        Ok(())
+    }
+}
+
+/// The format is a header of magic, version, shard amount and hasher id,
+/// followed by one checksummed section per shard.
+impl<K, V, S> ShardMap<K, V, S>
+where
+    K: Codec + Eq + Hash,
+    V: Codec,
+    S: BuildHasher + Clone,
+{
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_LEN + 8);
+        header.extend_from_slice(&MAGIC);
+        VERSION.encode(&mut header);
+        self.shards.len().encode(&mut header);
+        hasher_id(&self.hash_builder).encode(&mut header);
+        self.len().encode(&mut header);
+        checksum(&header).encode(&mut header);
+        writer.write_all(&header)?;
+
+        let mut payload = Vec::new();
+        for shard in &self.shards {
+            payload.clear();
+            for (k, v) in shard {
+                k.encode(&mut payload);
+                v.encode(&mut payload);
+            }
+            let mut section = Vec::with_capacity(24);
+            shard.len().encode(&mut section);
+            payload.len().encode(&mut section);
+            section_checksum(&section, &payload).encode(&mut section);
+            writer.write_all(&section)?;
+            writer.write_all(&payload)?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from(reader: impl Read) -> io::Result<Self>
+    where
+        S: Default,
+    {
+        Self::read_from_with_hasher(reader, S::default())
+    }
+
+    /// Each section is decoded as soon as it is read, so the payload of
+    /// only one shard is held in memory at a time.
+    pub fn read_from_with_hasher(mut reader: impl Read, hash_builder: S) -> io::Result<Self> {
+        let header = Header::read(&mut reader)?;
+        let direct = header.direct(&hash_builder);
+        let shard_amount = header.shard_amount;
+        // the shard amount is only trusted once that many sections were
+        // read, so shards are created as entries arrive and filled in at
+        // the end.
+        let mut shards = BTreeMap::new();
+        let mut count = 0usize;
+        for idx in 0..shard_amount {
+            let section = Section::read(&mut reader)?;
+            count = count.checked_add(section.len).ok_or_else(count_mismatch)?;
+            if direct {
+                let shard =
+                    HashMap::with_capacity_and_hasher(section.capacity(), hash_builder.clone());
+                shards.insert(idx, shard);
+            }
+            section.decode(|k, v| {
+                let hash = hash_builder.hash_one(&k);
+                let shard = shard_index(hash, shard_amount);
+                if direct && shard != idx {
+                    return Err(misplaced());
+                }
+                let shard = shards
+                    .entry(shard)
+                    .or_insert_with(|| HashMap::with_hasher(hash_builder.clone()));
+                insert_hashed(shard, hash, k, v);
+                Ok(())
+            })?;
+        }
+        if count != header.len {
+            return Err(count_mismatch());
+        }
+        Ok(Self {
+            shards: (0..shard_amount)
+                .map(|idx| {
+                    shards
+                        .remove(&idx)
+                        .unwrap_or_else(|| HashMap::with_hasher(hash_builder.clone()))
+                })
+                .collect(),
+            hash_builder,
+            poison_policy: PoisonPolicy::default(),
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{checksum, HEADER_LEN};
+    use crate::{MutableShardMap, ShardMap};
+    use std::hash::BuildHasherDefault;
+    use std::hash::DefaultHasher;
+    use std::io;
+
+    fn section_end(bytes: &[u8], start: usize) -> usize {
+        let size = u64::from_le_bytes(bytes[start + 8..start + 16].try_into().unwrap());
+        start + 24 + size as usize
+    }
+
+    fn damage_section_len(bytes: &[u8]) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        let second = section_end(&bytes, HEADER_LEN + 8);
+        bytes[second..second + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        bytes
+    }
+
+    fn inflate_shard_amount(bytes: &[u8]) -> Vec<u8> {
+        let mut header = bytes[..HEADER_LEN].to_vec();
+        header[8..16].copy_from_slice(&(1u64 << 60).to_le_bytes());
+        let sum = checksum(&header);
+        header.extend_from_slice(&sum.to_le_bytes());
+        header.extend_from_slice(&bytes[HEADER_LEN + 8..]);
+        header
+    }
+
+    #[test]
+    fn test_write_read() {
+        const N: usize = 10000;
+        let map: ShardMap<String, u64> = (0..N)
+            .map(|i| (i.to_string(), i as u64))
+            .collect::<MutableShardMap<_, _>>()
+            .into();
+        let mut strings = Vec::new();
+        map.write_to(&mut strings).unwrap();
+
+        let loaded = ShardMap::<String, u64>::read_from(strings.as_slice()).unwrap();
+        assert_eq!(loaded.shard_amount(), map.shard_amount());
+        assert_eq!(loaded.len(), N);
+        for i in 0..N {
+            assert_eq!(loaded.get(&i.to_string()), Some(&(i as u64)));
+        }
+
+        type Fixed = BuildHasherDefault<DefaultHasher>;
+        let map = ShardMap::<usize, Vec<u8>, Fixed>::from_iter((0..N).map(|i| (i, vec![i as u8])));
+        let mut bytes = Vec::new();
+        map.write_to(&mut bytes).unwrap();
+        let loaded = ShardMap::<usize, Vec<u8>, Fixed>::read_from(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.len(), N);
+        assert!(loaded.shards.iter().zip(&map.shards).all(|(a, b)| a == b));
+        #[cfg(feature = "rayon")]
+        {
+            let loaded =
+                ShardMap::<usize, Vec<u8>, Fixed>::par_read_from(bytes.as_slice()).unwrap();
+            assert!(loaded.shards.iter().zip(&map.shards).all(|(a, b)| a == b));
+            let loaded = ShardMap::<String, u64>::par_read_from(strings.as_slice()).unwrap();
+            assert_eq!(loaded.len(), N);
+            assert_eq!(loaded.get("42"), Some(&42));
+        }
+
+        // the section and header fields that size the decoding are covered by
+        // the checksums, and a shard amount must be backed by sections.
+        let (corrupted, huge) = (damage_section_len(&bytes), inflate_shard_amount(&bytes));
+        for input in [&corrupted, &huge] {
+            let err = ShardMap::<usize, Vec<u8>, Fixed>::read_from(input.as_slice());
+            assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+            #[cfg(feature = "rayon")]
+            {
+                let err = ShardMap::<usize, Vec<u8>, Fixed>::par_read_from(input.as_slice());
+                assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+            }
+        }
+        let (corrupted, huge) = (damage_section_len(&strings), inflate_shard_amount(&strings));
+        for input in [&corrupted, &huge] {
+            let err = ShardMap::<String, u64>::read_from(input.as_slice());
+            assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+
+        // intact sections stored in the wrong order must not load as shards.
+        let first = HEADER_LEN + 8;
+        let second = section_end(&bytes, first);
+        let third = section_end(&bytes, second);
+        let mut swapped = bytes[..first].to_vec();
+        swapped.extend_from_slice(&bytes[second..third]);
+        swapped.extend_from_slice(&bytes[first..second]);
+        swapped.extend_from_slice(&bytes[third..]);
+        let err = ShardMap::<usize, Vec<u8>, Fixed>::read_from(swapped.as_slice());
+        assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        #[cfg(feature = "rayon")]
+        {
+            let err = ShardMap::<usize, Vec<u8>, Fixed>::par_read_from(swapped.as_slice());
+            assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        }
+
+        let err = ShardMap::<usize, Vec<u8>, Fixed>::read_from(&bytes[..bytes.len() - 1]);
+        assert_eq!(err.err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        let err = ShardMap::<usize, Vec<u8>, Fixed>::read_from(bytes.as_slice());
+        assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+        bytes[0] = b'X';
+        let err = ShardMap::<usize, Vec<u8>, Fixed>::read_from(bytes.as_slice());
+        assert_eq!(err.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
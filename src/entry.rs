@@ -0,0 +1,155 @@
+use crate::guard::RefMut;
+use crate::HashMap;
+use hashbrown::hash_map::RawEntryMut;
+use std::hash::{BuildHasher, Hash};
+use std::ops::DerefMut;
+
+pub enum Entry<K, V, G> {
+    Occupied(OccupiedEntry<K, V, G>),
+    Vacant(VacantEntry<K, V, G>),
+}
+
+pub struct OccupiedEntry<K, V, G> {
+    guard: G,
+    hash: u64,
+    key: *const K,
+    value: *mut V,
+}
+
+pub struct VacantEntry<K, V, G> {
+    guard: G,
+    hash: u64,
+    key: K,
+    _value: std::marker::PhantomData<V>,
+}
+
+impl<K, V, S, G> Entry<K, V, G>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+    G: DerefMut<Target = HashMap<K, V, S>>,
+{
+    pub(crate) fn new(mut guard: G, hash: u64, key: K) -> Self {
+        match guard.raw_entry_mut().from_key_hashed_nocheck(hash, &key) {
+            RawEntryMut::Occupied(entry) => {
+                let (k, v) = entry.into_key_value();
+                let (key, value) = (k as *const K, v as *mut V);
+                Entry::Occupied(OccupiedEntry {
+                    guard,
+                    hash,
+                    key,
+                    value,
+                })
+            }
+            RawEntryMut::Vacant(_) => Entry::Vacant(VacantEntry {
+                guard,
+                hash,
+                key,
+                _value: Default::default(),
+            }),
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+
+    pub fn or_insert(self, value: V) -> RefMut<K, V, G> {
+        self.or_insert_with(|| value)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> RefMut<K, V, G> {
+        match self {
+            Entry::Occupied(entry) => entry.into_ref(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    pub fn or_default(self) -> RefMut<K, V, G>
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+}
+
+impl<K, V, S, G> OccupiedEntry<K, V, G>
+where
+    S: BuildHasher,
+    G: DerefMut<Target = HashMap<K, V, S>>,
+{
+    pub fn key(&self) -> &K {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.key }
+    }
+
+    pub fn get(&self) -> &V {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.value }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &mut *self.value }
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn into_ref(self) -> RefMut<K, V, G> {
+        // SAFETY: the pointers come from the map locked by the guard.
+        unsafe { RefMut::new(self.guard, self.key, self.value) }
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(mut self) -> (K, V) {
+        let key = self.key;
+        match self
+            .guard
+            .raw_entry_mut()
+            .from_hash(self.hash, |k| std::ptr::eq(k, key))
+        {
+            RawEntryMut::Occupied(entry) => entry.remove_entry(),
+            RawEntryMut::Vacant(_) => unreachable!(),
+        }
+    }
+}
+
+impl<K, V, S, G> VacantEntry<K, V, G>
+where
+    K: Hash,
+    S: BuildHasher,
+    G: DerefMut<Target = HashMap<K, V, S>>,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(mut self, value: V) -> RefMut<K, V, G> {
+        let (k, v) = match self.guard.raw_entry_mut().from_hash(self.hash, |_| false) {
+            RawEntryMut::Vacant(entry) => entry.insert_hashed_nocheck(self.hash, self.key, value),
+            RawEntryMut::Occupied(_) => unreachable!(),
+        };
+        let (key, value) = (k as *const K, v as *mut V);
+        // SAFETY: the pointers come from the map locked by the guard.
+        unsafe { RefMut::new(self.guard, key, value) }
+    }
+}
@@ -0,0 +1,54 @@
+use crate::HashMap;
+use std::fmt;
+use std::sync::LockResult;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    Poisoned,
+    WouldBlock,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Poisoned => f.write_str("shard lock poisoned"),
+            Error::WouldBlock => f.write_str("shard lock would block"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// What to do with a shard whose lock was poisoned by a panicking holder.
+///
+/// `is_empty`, `len` and `contains_key` read a poisoned shard as it is; their
+/// `try_` forms apply the policy. Owned bulk loads, `Extend` on an owned map
+/// and deserializing into a seed map, always recover poisoned shards.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PoisonPolicy {
+    /// Fail with [`Error::Poisoned`]; the infallible methods panic.
+    #[default]
+    Propagate,
+    /// Clear the poison flag and keep using the shard contents.
+    Recover,
+    /// Clear the poison flag and drop every entry of the shard.
+    Clear,
+}
+
+impl PoisonPolicy {
+    pub(crate) fn into_inner<K, V, S>(
+        self,
+        result: LockResult<HashMap<K, V, S>>,
+    ) -> Result<HashMap<K, V, S>, Error> {
+        match (self, result) {
+            (_, Ok(map)) => Ok(map),
+            (PoisonPolicy::Propagate, Err(_)) => Err(Error::Poisoned),
+            (PoisonPolicy::Recover, Err(err)) => Ok(err.into_inner()),
+            (PoisonPolicy::Clear, Err(err)) => {
+                let mut map = err.into_inner();
+                map.clear();
+                Ok(map)
+            }
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{HashMap, MutableShardMap, ShardMap};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash};
+
+/// A frozen map kept in a single open-addressed table.
+///
+/// Without writers the shards no longer prevent contention, so lookups skip
+/// the shard selection and go straight to one contiguous table. The read
+/// methods mirror those of a frozen [`ShardMap`].
+pub struct FlatFrozenMap<K, V, S> {
+    table: HashMap<K, V, S>,
+}
+
+impl<K, V, S: BuildHasher> FlatFrozenMap<K, V, S> {
+    pub fn hasher(&self) -> &S {
+        self.table.hasher()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.table.contains_key(k)
+    }
+
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.table.get(k)
+    }
+
+    pub fn iter(&self) -> hashbrown::hash_map::Iter<'_, K, V> {
+        self.table.iter()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.table.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.table.values()
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> From<ShardMap<K, V, S>> for FlatFrozenMap<K, V, S> {
+    fn from(from: ShardMap<K, V, S>) -> Self {
+        let len = from.shards.iter().map(|m| m.len()).sum();
+        let mut table = HashMap::with_capacity_and_hasher(len, from.hash_builder);
+        table.extend(from.shards.into_iter().flatten());
+        Self { table }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher> MutableShardMap<K, V, S> {
+    pub fn freeze_flat(self) -> FlatFrozenMap<K, V, S> {
+        ShardMap::from(self).into()
+    }
+}
+
+impl<'a, K, V, S> IntoIterator for &'a FlatFrozenMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = hashbrown::hash_map::Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.table.iter()
+    }
+}
+
+impl<K, V, S> IntoIterator for FlatFrozenMap<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = hashbrown::hash_map::IntoIter<K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.table.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::MutableShardMap;
+
+    #[test]
+    fn test_freeze_flat() {
+        const N: usize = 1 << 15;
+        let map = MutableShardMap::<usize, usize>::default();
+        for i in 0..N {
+            map.insert(i, i * 2);
+        }
+        let flat = map.freeze_flat();
+        assert!(!flat.is_empty());
+        assert_eq!(flat.len(), N);
+        for i in 0..N {
+            assert_eq!(flat.get(&i), Some(&(i * 2)));
+        }
+        assert!(flat.contains_key(&7));
+        assert!(!flat.contains_key(&N));
+        assert_eq!(flat.keys().sum::<usize>(), (0..N).sum::<usize>());
+        assert_eq!(
+            flat.values().sum::<usize>(),
+            (0..N).map(|i| i * 2).sum::<usize>()
+        );
+        assert_eq!((&flat).into_iter().count(), N);
+
+        let mut entries = flat.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert!(entries.into_iter().eq((0..N).map(|i| (i, i * 2))));
+    }
+}
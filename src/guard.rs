@@ -0,0 +1,84 @@
+use std::ops::{Deref, DerefMut};
+
+pub struct RefMut<K, V, G> {
+    _guard: G,
+    key: *const K,
+    value: *mut V,
+}
+
+impl<K, V, G> RefMut<K, V, G> {
+    // SAFETY: `key` and `value` must point into the map locked by `guard`,
+    // and the map must not be modified while the returned value is alive.
+    pub(crate) unsafe fn new(guard: G, key: *const K, value: *mut V) -> Self {
+        Self {
+            _guard: guard,
+            key,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.key }
+    }
+
+    pub fn value(&self) -> &V {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.value }
+    }
+
+    pub fn value_mut(&mut self) -> &mut V {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &mut *self.value }
+    }
+}
+
+impl<K, V, G> Deref for RefMut<K, V, G> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.value()
+    }
+}
+
+impl<K, V, G> DerefMut for RefMut<K, V, G> {
+    fn deref_mut(&mut self) -> &mut V {
+        self.value_mut()
+    }
+}
+
+pub struct Ref<K, V, G> {
+    _guard: G,
+    key: *const K,
+    value: *const V,
+}
+
+impl<K, V, G> Ref<K, V, G> {
+    // SAFETY: `key` and `value` must point into the map locked by `guard`,
+    // and the map must not be modified while the returned value is alive.
+    pub(crate) unsafe fn new(guard: G, key: *const K, value: *const V) -> Self {
+        Self {
+            _guard: guard,
+            key,
+            value,
+        }
+    }
+
+    pub fn key(&self) -> &K {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.key }
+    }
+
+    pub fn value(&self) -> &V {
+        // SAFETY: the guard keeps the shard locked and the entry in place.
+        unsafe { &*self.value }
+    }
+}
+
+impl<K, V, G> Deref for Ref<K, V, G> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        self.value()
+    }
+}
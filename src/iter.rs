@@ -0,0 +1,105 @@
+use crate::{ImmutableInnerMap, InnerMap, MutableInnerMap, ShardMap};
+use std::hash::{BuildHasher, Hash};
+use std::iter::{FlatMap, Flatten};
+use std::{slice, vec};
+
+type ShardIter<'a, T, I> = FlatMap<slice::Iter<'a, T>, I, fn(&'a T) -> I>;
+
+pub struct Iter<'a, K: 'a, V: 'a, S, T: ImmutableInnerMap<K, V, S> + 'a> {
+    inner: ShardIter<'a, T, T::Iter<'a>>,
+}
+
+impl<'a, K, V, S, T: ImmutableInnerMap<K, V, S>> Iter<'a, K, V, S, T> {
+    pub(crate) fn new(shards: &'a [T]) -> Self {
+        let f: fn(&'a T) -> T::Iter<'a> = T::iter;
+        Self {
+            inner: shards.iter().flat_map(f),
+        }
+    }
+}
+
+impl<'a, K, V, S, T: ImmutableInnerMap<K, V, S>> Iterator for Iter<'a, K, V, S, T> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+pub struct IntoIter<T: IntoIterator> {
+    inner: Flatten<vec::IntoIter<T>>,
+}
+
+impl<T: IntoIterator> Iterator for IntoIter<T> {
+    type Item = T::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, K, V, S, T: ImmutableInnerMap<K, V, S>> IntoIterator for &'a ShardMap<K, V, S, T> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V, S, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::new(&self.shards)
+    }
+}
+
+impl<K, V, S, T> IntoIterator for ShardMap<K, V, S, T>
+where
+    T: ImmutableInnerMap<K, V, S> + IntoIterator<Item = (K, V)>,
+{
+    type Item = (K, V);
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            inner: self.shards.into_iter().flatten(),
+        }
+    }
+}
+
+impl<K, V, S, T> Extend<(K, V)> for &ShardMap<K, V, S, T>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Clone,
+    T: MutableInnerMap<K, V, S>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.insert_many(iter);
+    }
+}
+
+impl<K, V, S, T> Extend<(K, V)> for ShardMap<K, V, S, T>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Clone,
+    T: InnerMap<K, V, S>,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.extend_shards(iter);
+    }
+}
+
+impl<K, V, S, T> FromIterator<(K, V)> for ShardMap<K, V, S, T>
+where
+    K: Eq + Hash,
+    S: BuildHasher + Clone + Default,
+    T: InnerMap<K, V, S>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut map = Self::default();
+        map.extend(iter);
+        map
+    }
+}
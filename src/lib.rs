@@ -1,87 +1,495 @@
+use hashbrown::hash_map::RawEntryMut;
 use std::borrow::Borrow;
-use std::collections::HashMap;
-use std::hash::{DefaultHasher, Hash, Hasher};
-use std::sync::Mutex;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::ops::{Deref, DerefMut};
+use std::sync::{
+    LockResult, Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    TryLockError, TryLockResult,
+};
+use std::time::{Duration, Instant};
 use std::{marker::PhantomData, sync::OnceLock};
 
-pub trait InnerMap<K, V>: Default {
+mod codec;
+mod entry;
+mod error;
+mod flat;
+mod guard;
+mod iter;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "parking_lot")]
+mod parking_lot;
+mod perfect;
+#[cfg(feature = "rayon")]
+mod rayon;
+#[cfg(feature = "serde")]
+mod serde;
+mod sorted;
+
+pub use codec::Codec;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::{Error, PoisonPolicy};
+pub use flat::FlatFrozenMap;
+pub use guard::{Ref, RefMut};
+pub use hashbrown::HashMap;
+pub use iter::{IntoIter, Iter};
+#[cfg(feature = "mmap")]
+pub use mmap::MappedShardMap;
+#[cfg(feature = "parking_lot")]
+pub use parking_lot::{ParkingLotRwShardMap, ParkingLotShardMap};
+pub use perfect::PerfectHashShard;
+#[cfg(feature = "rayon")]
+pub use rayon::{ParIntoIter, ParIter};
+pub use sorted::{SortedIter, SortedShard};
+
+pub trait InnerMap<K, V, S> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self;
     fn is_empty(&self) -> bool;
     fn len(&self) -> usize;
-    fn contains_key<Q>(&self, k: &Q) -> bool
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized;
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher;
+
+    // shards that are slow to grow one entry at a time set this, so bulk
+    // loads buffer all entries of a shard and hand them over in one go.
+    const BULK_BUILD: bool = false;
+
+    // later entries win over earlier ones with the same key.
+    fn extend_mut(&mut self, entries: impl IntoIterator<Item = (u64, K, V)>)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        for (hash, k, v) in entries {
+            self.insert_mut(hash, k, v);
+        }
+    }
 }
 
-pub trait ImmutableInnerMap<K, V>: InnerMap<K, V> {
-    fn get<Q>(&self, k: &Q) -> Option<&V>
+pub trait ImmutableInnerMap<K, V, S>: InnerMap<K, V, S> {
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn iter(&self) -> Self::Iter<'_>;
+    fn get<Q>(&self, hash: u64, k: &Q) -> Option<&V>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized;
 }
 
-pub trait MutableInnerMap<K, V>: InnerMap<K, V> {
-    fn get<Q>(&self, k: &Q) -> Option<V>
+pub trait MutableInnerMap<K, V, S>: InnerMap<K, V, S> {
+    // the locked map must stay at the same address while a guard is moved.
+    type Guard<'a>: DerefMut<Target = HashMap<K, V, S>>
+    where
+        Self: 'a;
+    type ReadGuard<'a>: Deref<Target = HashMap<K, V, S>>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>>;
+    fn read(&self) -> LockResult<Self::ReadGuard<'_>>;
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>>;
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>>;
+
+    fn clear_poison(&self) {}
+
+    // backends without timed locks poll until the deadline.
+    fn try_lock_for(&self, timeout: Duration) -> TryLockResult<Self::Guard<'_>> {
+        poll_for(timeout, || self.try_lock())
+    }
+
+    fn try_read_for(&self, timeout: Duration) -> TryLockResult<Self::ReadGuard<'_>> {
+        poll_for(timeout, || self.try_read())
+    }
+
+    fn lock_with(&self, policy: PoisonPolicy) -> Result<Self::Guard<'_>, Error> {
+        self.lock()
+            .or_else(|err| recover(policy, err, || self.clear_poison()))
+    }
+
+    fn read_with(&self, policy: PoisonPolicy) -> Result<Self::ReadGuard<'_>, Error> {
+        match self.read() {
+            Ok(map) => Ok(map),
+            Err(_) if policy == PoisonPolicy::Propagate => Err(Error::Poisoned),
+            Err(err) if policy == PoisonPolicy::Recover => {
+                self.clear_poison();
+                Ok(err.into_inner())
+            }
+            Err(err) => {
+                // clearing the shard needs the exclusive lock.
+                drop(err);
+                drop(self.lock_with(policy)?);
+                self.read_with(policy)
+            }
+        }
+    }
+
+    fn try_lock_with(
+        &self,
+        policy: PoisonPolicy,
+        timeout: Duration,
+    ) -> Result<Self::Guard<'_>, Error> {
+        match self.try_lock_for(timeout) {
+            Ok(map) => Ok(map),
+            Err(TryLockError::WouldBlock) => Err(Error::WouldBlock),
+            Err(TryLockError::Poisoned(err)) => recover(policy, err, || self.clear_poison()),
+        }
+    }
+
+    fn try_read_with(
+        &self,
+        policy: PoisonPolicy,
+        timeout: Duration,
+    ) -> Result<Self::ReadGuard<'_>, Error> {
+        match self.try_read_for(timeout) {
+            Ok(map) => Ok(map),
+            Err(TryLockError::WouldBlock) => Err(Error::WouldBlock),
+            Err(TryLockError::Poisoned(_)) if policy == PoisonPolicy::Propagate => {
+                Err(Error::Poisoned)
+            }
+            Err(TryLockError::Poisoned(err)) if policy == PoisonPolicy::Recover => {
+                self.clear_poison();
+                Ok(err.into_inner())
+            }
+            Err(TryLockError::Poisoned(err)) => {
+                drop(err);
+                drop(self.try_lock_with(policy, timeout)?);
+                self.try_read_with(policy, timeout)
+            }
+        }
+    }
+
+    fn get_with<Q, R, F>(
+        &self,
+        policy: PoisonPolicy,
+        hash: u64,
+        k: &Q,
+        f: F,
+    ) -> Result<Option<R>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        let map = self.read_with(policy)?;
+        Ok(map
+            .raw_entry()
+            .from_key_hashed_nocheck(hash, k)
+            .map(|(_, v)| f(v)))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn get_ref<Q>(
+        &self,
+        policy: PoisonPolicy,
+        hash: u64,
+        k: &Q,
+    ) -> Result<Option<Ref<K, V, Self::ReadGuard<'_>>>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.read_with(policy)?;
+        let Some((key, value)) = map.raw_entry().from_key_hashed_nocheck(hash, k) else {
+            return Ok(None);
+        };
+        let (key, value) = (key as *const K, value as *const V);
+        // SAFETY: the pointers come from the map locked by the guard.
+        Ok(Some(unsafe { Ref::new(map, key, value) }))
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn get_mut<Q>(
+        &self,
+        policy: PoisonPolicy,
+        hash: u64,
+        k: &Q,
+    ) -> Result<Option<RefMut<K, V, Self::Guard<'_>>>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut map = self.lock_with(policy)?;
+        let (key, value) = match map.raw_entry_mut().from_key_hashed_nocheck(hash, k) {
+            RawEntryMut::Occupied(entry) => entry.into_key_value(),
+            RawEntryMut::Vacant(_) => return Ok(None),
+        };
+        let (key, value) = (key as *const K, value as *mut V);
+        // SAFETY: the pointers come from the map locked by the guard.
+        Ok(Some(unsafe { RefMut::new(map, key, value) }))
+    }
+
+    fn get<Q>(&self, policy: PoisonPolicy, hash: u64, k: &Q) -> Result<Option<V>, Error>
     where
         K: Borrow<Q> + Eq + Hash,
         V: Clone,
-        Q: Eq + Hash + ?Sized;
-    fn insert(&self, k: K, v: V) -> Option<V>
+        Q: Eq + Hash + ?Sized,
+    {
+        self.get_with(policy, hash, k, V::clone)
+    }
+
+    fn insert(&self, policy: PoisonPolicy, hash: u64, k: K, v: V) -> Result<Option<V>, Error>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        let mut map = self.lock_with(policy)?;
+        Ok(insert_hashed(&mut map, hash, k, v))
+    }
+
+    fn remove<Q>(&self, policy: PoisonPolicy, hash: u64, k: &Q) -> Result<Option<V>, Error>
     where
-        K: Eq + Hash;
-    fn remove<Q>(&self, k: &Q) -> Option<V>
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut map = self.lock_with(policy)?;
+        Ok(remove_hashed(&mut map, hash, k))
+    }
+
+    fn clear(&self, policy: PoisonPolicy) -> Result<(), Error> {
+        self.lock_with(policy)?.clear();
+        Ok(())
+    }
+
+    fn is_empty_with(&self, policy: PoisonPolicy) -> Result<bool, Error> {
+        Ok(self.read_with(policy)?.is_empty())
+    }
+
+    fn len_with(&self, policy: PoisonPolicy) -> Result<usize, Error> {
+        Ok(self.read_with(policy)?.len())
+    }
+
+    fn contains_key_with<Q>(&self, policy: PoisonPolicy, hash: u64, k: &Q) -> Result<bool, Error>
     where
         K: Borrow<Q> + Eq + Hash,
-        Q: Eq + Hash + ?Sized;
-    fn clear(&self);
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.read_with(policy)?;
+        Ok(map.raw_entry().from_key_hashed_nocheck(hash, k).is_some())
+    }
+}
+
+// the inner tables probe with the low bits and tag with the top 7 bits of the
+// same hash, so the shard index is taken from the bits in between.
+#[inline(always)]
+pub(crate) fn shard_index(hash: u64, shard_amount: usize) -> usize {
+    (((hash << 7) as u128 * shard_amount as u128) >> 64) as usize
+}
+
+// the murmur3 finalizer, for scrambling hashes that may be weak in some bits.
+#[inline(always)]
+pub(crate) fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+// sleeps between attempts with a growing pause, so waiting out a long timeout
+// does not keep a core busy.
+fn poll_for<G>(
+    timeout: Duration,
+    mut try_lock: impl FnMut() -> TryLockResult<G>,
+) -> TryLockResult<G> {
+    const MAX_PAUSE: Duration = Duration::from_millis(1);
+    let deadline = Instant::now() + timeout;
+    let mut pause = Duration::from_micros(1);
+    loop {
+        match try_lock() {
+            Err(TryLockError::WouldBlock) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(TryLockError::WouldBlock);
+                }
+                std::thread::sleep(pause.min(deadline - now));
+                pause = (pause * 2).min(MAX_PAUSE);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn recover<K, V, S, G>(
+    policy: PoisonPolicy,
+    err: PoisonError<G>,
+    clear_poison: impl FnOnce(),
+) -> Result<G, Error>
+where
+    G: DerefMut<Target = HashMap<K, V, S>>,
+{
+    if policy == PoisonPolicy::Propagate {
+        return Err(Error::Poisoned);
+    }
+    let mut map = err.into_inner();
+    if policy == PoisonPolicy::Clear {
+        map.clear();
+    }
+    clear_poison();
+    Ok(map)
+}
+
+pub(crate) fn insert_hashed<K: Eq + Hash, V, S: BuildHasher>(
+    map: &mut HashMap<K, V, S>,
+    hash: u64,
+    k: K,
+    v: V,
+) -> Option<V> {
+    match map.raw_entry_mut().from_key_hashed_nocheck(hash, &k) {
+        RawEntryMut::Occupied(mut entry) => Some(entry.insert(v)),
+        RawEntryMut::Vacant(entry) => {
+            entry.insert_hashed_nocheck(hash, k, v);
+            None
+        }
+    }
+}
+
+fn remove_hashed<K, V, S, Q>(map: &mut HashMap<K, V, S>, hash: u64, k: &Q) -> Option<V>
+where
+    K: Borrow<Q>,
+    Q: Eq + ?Sized,
+{
+    match map.raw_entry_mut().from_key_hashed_nocheck(hash, k) {
+        RawEntryMut::Occupied(entry) => Some(entry.remove()),
+        RawEntryMut::Vacant(_) => None,
+    }
 }
 
-pub struct ShardMap<K, V, T: InnerMap<K, V> = HashMap<K, V>> {
+pub struct ShardMap<K, V, S = RandomState, T: InnerMap<K, V, S> = HashMap<K, V, S>> {
     shards: Vec<T>,
-    _phantom_data: PhantomData<(K, V)>,
+    hash_builder: S,
+    poison_policy: PoisonPolicy,
+    _phantom_data: PhantomData<fn() -> (K, V)>,
 }
 
-pub type MutableShardMap<K, V> = ShardMap<K, V, Mutex<HashMap<K, V>>>;
+pub type MutableShardMap<K, V, S = RandomState> = ShardMap<K, V, S, Mutex<HashMap<K, V, S>>>;
+pub type RwShardMap<K, V, S = RandomState> = ShardMap<K, V, S, RwLock<HashMap<K, V, S>>>;
 
-fn default_shard_amount() -> usize {
+pub(crate) fn default_shard_amount() -> usize {
     static DEFAULT_SHARD_AMOUNT: OnceLock<usize> = OnceLock::new();
     *DEFAULT_SHARD_AMOUNT.get_or_init(|| {
         (std::thread::available_parallelism().map_or(1, usize::from) * 4).next_power_of_two()
     })
 }
 
-impl<K: Eq + Hash, V, T: InnerMap<K, V>> Default for ShardMap<K, V, T> {
+impl<K: Eq + Hash, V, S: BuildHasher + Clone + Default, T: InnerMap<K, V, S>> Default
+    for ShardMap<K, V, S, T>
+{
     fn default() -> Self {
-        Self::new()
+        Self::with_hasher(S::default())
     }
 }
 
-impl<K, V, T: InnerMap<K, V>> ShardMap<K, V, T> {
+impl<K, V, T: InnerMap<K, V, RandomState>> ShardMap<K, V, RandomState, T> {
     pub fn new() -> Self {
-        let n = default_shard_amount();
+        Self::with_hasher(RandomState::new())
+    }
+
+    pub fn with_shard_amount(shard_amount: usize) -> Self {
+        Self::with_shard_amount_and_hasher(shard_amount, RandomState::new())
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, RandomState::new())
+    }
+
+    pub fn with_capacity_and_shard_amount(capacity: usize, shard_amount: usize) -> Self {
+        Self::with_capacity_and_shard_amount_and_hasher(capacity, shard_amount, RandomState::new())
+    }
+}
+
+impl<K, V, S: BuildHasher + Clone, T: InnerMap<K, V, S>> ShardMap<K, V, S, T> {
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_shard_amount_and_hasher(default_shard_amount(), hash_builder)
+    }
+
+    pub fn with_shard_amount_and_hasher(shard_amount: usize, hash_builder: S) -> Self {
+        Self::with_capacity_and_shard_amount_and_hasher(0, shard_amount, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::with_capacity_and_shard_amount_and_hasher(
+            capacity,
+            default_shard_amount(),
+            hash_builder,
+        )
+    }
+
+    pub fn with_capacity_and_shard_amount_and_hasher(
+        capacity: usize,
+        shard_amount: usize,
+        hash_builder: S,
+    ) -> Self {
+        assert!(shard_amount > 0, "shard amount must be greater than zero");
+        let shard_capacity = capacity.div_ceil(shard_amount);
         Self {
-            shards: (0..n).map(|_| Default::default()).collect(),
+            shards: (0..shard_amount)
+                .map(|_| T::with_capacity_and_hasher(shard_capacity, hash_builder.clone()))
+                .collect(),
+            hash_builder,
+            poison_policy: PoisonPolicy::default(),
             _phantom_data: Default::default(),
         }
     }
 
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    pub fn shard_amount(&self) -> usize {
+        self.shards.len()
+    }
+
     pub fn is_empty(&self) -> bool {
         self.shards.iter().all(|m| m.is_empty())
     }
 
     pub fn len(&self) -> usize {
-        self.shards.iter().map(|m| m.len()).sum::<usize>()
+        self.shards.iter().map(|m| m.len()).sum()
     }
 
     #[inline(always)]
-    fn shard<Q>(&self, k: &Q) -> usize
+    fn hash<Q>(&self, k: &Q) -> u64
     where
         K: Borrow<Q>,
-        Q: Eq + Hash + ?Sized,
+        Q: Hash + ?Sized,
+    {
+        self.hash_builder.hash_one(k)
+    }
+
+    #[inline(always)]
+    fn shard(&self, hash: u64) -> usize {
+        shard_index(hash, self.shards.len())
+    }
+
+    fn extend_shards(&mut self, iter: impl IntoIterator<Item = (K, V)>)
+    where
+        K: Eq + Hash,
     {
-        let mut s = DefaultHasher::new();
-        k.hash(&mut s);
-        s.finish() as usize % self.shards.len()
+        if !T::BULK_BUILD {
+            for (k, v) in iter {
+                let hash = self.hash(&k);
+                let idx = self.shard(hash);
+                self.shards[idx].insert_mut(hash, k, v);
+            }
+            return;
+        }
+        let mut buckets = (0..self.shards.len()).map(|_| vec![]).collect::<Vec<_>>();
+        for (k, v) in iter {
+            let hash = self.hash(&k);
+            buckets[self.shard(hash)].push((hash, k, v));
+        }
+        for (shard, entries) in self.shards.iter_mut().zip(buckets) {
+            shard.extend_mut(entries);
+        }
     }
 
     pub fn contains_key<Q>(&self, k: &Q) -> bool
@@ -89,178 +497,882 @@ impl<K, V, T: InnerMap<K, V>> ShardMap<K, V, T> {
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
     {
-        self.shards[self.shard(k)].contains_key(k)
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].contains_key(hash, k)
     }
 }
 
-impl<K, V, T: ImmutableInnerMap<K, V>> ShardMap<K, V, T> {
+impl<K, V, S: BuildHasher + Clone, T: ImmutableInnerMap<K, V, S>> ShardMap<K, V, S, T> {
     pub fn get<Q>(&self, k: &Q) -> Option<&V>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
     {
-        self.shards[self.shard(k)].get(k)
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].get(hash, k)
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V, S, T> {
+        Iter::new(&self.shards)
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
     }
 }
 
-impl<K, V, T: MutableInnerMap<K, V>> ShardMap<K, V, T> {
+impl<K, V, S: BuildHasher + Clone, T: MutableInnerMap<K, V, S>> ShardMap<K, V, S, T> {
+    pub fn poison_policy(&self) -> PoisonPolicy {
+        self.poison_policy
+    }
+
+    pub fn set_poison_policy(&mut self, policy: PoisonPolicy) {
+        self.poison_policy = policy;
+    }
+
+    /// Like [`ShardMap::is_empty`], but applies the poison policy instead of
+    /// reading poisoned shards as they are.
+    pub fn try_is_empty(&self) -> Result<bool, Error> {
+        for m in &self.shards {
+            if !m.is_empty_with(self.poison_policy)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub fn try_len(&self) -> Result<usize, Error> {
+        self.shards
+            .iter()
+            .map(|m| m.len_with(self.poison_policy))
+            .sum()
+    }
+
+    pub fn try_contains_key<Q>(&self, k: &Q) -> Result<bool, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].contains_key_with(self.poison_policy, hash, k)
+    }
+
     pub fn get_cloned<Q>(&self, k: &Q) -> Option<V>
     where
         K: Borrow<Q> + Eq + Hash,
         V: Clone,
         Q: Eq + Hash + ?Sized,
     {
-        self.shards[self.shard(k)].get(k)
+        self.try_get_cloned(k).unwrap()
     }
 
-    pub fn insert(&self, k: K, v: V) -> Option<V>
+    pub fn try_get_cloned<Q>(&self, k: &Q) -> Result<Option<V>, Error>
     where
-        K: Eq + Hash,
+        K: Borrow<Q> + Eq + Hash,
+        V: Clone,
+        Q: Eq + Hash + ?Sized,
     {
-        let idx = self.shard(&k);
-        self.shards[idx].insert(k, v)
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].get(self.poison_policy, hash, k)
     }
 
-    pub fn remove<Q>(&self, k: &Q) -> Option<V>
+    pub fn get_with<Q, R, F>(&self, k: &Q, f: F) -> Option<R>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
+        F: FnOnce(&V) -> R,
     {
-        let idx = self.shard(k);
-        self.shards[idx].remove(k)
+        self.try_get_with(k, f).unwrap()
     }
 
-    pub fn clear(&self) {
-        for shard in &self.shards {
-            shard.clear();
-        }
+    pub fn try_get_with<Q, R, F>(&self, k: &Q, f: F) -> Result<Option<R>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].get_with(self.poison_policy, hash, k, f)
     }
-}
 
-impl<K, V> InnerMap<K, V> for HashMap<K, V> {
-    fn is_empty(&self) -> bool {
-        self.is_empty()
+    pub fn get_ref<Q>(&self, k: &Q) -> Option<Ref<K, V, T::ReadGuard<'_>>>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.try_get_ref(k).unwrap()
     }
 
-    fn len(&self) -> usize {
-        self.len()
+    #[allow(clippy::type_complexity)]
+    pub fn try_get_ref<Q>(&self, k: &Q) -> Result<Option<Ref<K, V, T::ReadGuard<'_>>>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].get_ref(self.poison_policy, hash, k)
     }
 
-    fn contains_key<Q>(&self, k: &Q) -> bool
+    pub fn get_mut<Q>(&self, k: &Q) -> Option<RefMut<K, V, T::Guard<'_>>>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
     {
-        self.contains_key(k)
+        self.try_get_mut(k).unwrap()
     }
-}
 
-impl<K, V> ImmutableInnerMap<K, V> for HashMap<K, V> {
-    fn get<Q>(&self, k: &Q) -> Option<&V>
+    #[allow(clippy::type_complexity)]
+    pub fn try_get_mut<Q>(&self, k: &Q) -> Result<Option<RefMut<K, V, T::Guard<'_>>>, Error>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
     {
-        self.get(k)
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].get_mut(self.poison_policy, hash, k)
     }
-}
 
-impl<K, V> InnerMap<K, V> for Mutex<HashMap<K, V>> {
-    fn is_empty(&self) -> bool {
-        let map = self.lock().unwrap();
-        map.is_empty()
+    pub fn insert(&self, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+    {
+        self.try_insert(k, v).unwrap()
     }
 
-    fn len(&self) -> usize {
-        let map = self.lock().unwrap();
-        map.len()
+    pub fn try_insert(&self, k: K, v: V) -> Result<Option<V>, Error>
+    where
+        K: Eq + Hash,
+    {
+        let hash = self.hash(&k);
+        self.shards[self.shard(hash)].insert(self.poison_policy, hash, k, v)
     }
 
-    fn contains_key<Q>(&self, k: &Q) -> bool
+    pub fn remove<Q>(&self, k: &Q) -> Option<V>
     where
         K: Borrow<Q> + Eq + Hash,
         Q: Eq + Hash + ?Sized,
     {
-        let map = self.lock().unwrap();
-        map.contains_key(k)
+        self.try_remove(k).unwrap()
     }
-}
 
-impl<K, V> MutableInnerMap<K, V> for Mutex<HashMap<K, V>> {
-    fn get<Q>(&self, k: &Q) -> Option<V>
+    pub fn try_remove<Q>(&self, k: &Q) -> Result<Option<V>, Error>
     where
         K: Borrow<Q> + Eq + Hash,
-        V: Clone,
         Q: Eq + Hash + ?Sized,
     {
-        let map = self.lock().unwrap();
-        map.get(k).cloned()
+        let hash = self.hash(k);
+        self.shards[self.shard(hash)].remove(self.poison_policy, hash, k)
     }
 
-    fn insert(&self, k: K, v: V) -> Option<V>
+    pub fn get_cloned_nonblocking<Q>(&self, k: &Q) -> Result<Option<V>, Error>
     where
-        K: Eq + Hash,
+        K: Borrow<Q> + Eq + Hash,
+        V: Clone,
+        Q: Eq + Hash + ?Sized,
     {
-        let mut map = self.lock().unwrap();
-        map.insert(k, v)
+        self.get_cloned_timeout(k, Duration::ZERO)
     }
 
-    fn remove<Q>(&self, k: &Q) -> Option<V>
+    pub fn get_cloned_timeout<Q>(&self, k: &Q, timeout: Duration) -> Result<Option<V>, Error>
     where
         K: Borrow<Q> + Eq + Hash,
+        V: Clone,
         Q: Eq + Hash + ?Sized,
     {
-        let mut map = self.lock().unwrap();
-        map.remove(k)
+        let hash = self.hash(k);
+        let map = self.shards[self.shard(hash)].try_read_with(self.poison_policy, timeout)?;
+        Ok(map
+            .raw_entry()
+            .from_key_hashed_nocheck(hash, k)
+            .map(|(_, v)| v.clone()))
     }
 
-    fn clear(&self) {
-        let mut map = self.lock().unwrap();
-        map.clear()
+    pub fn insert_nonblocking(&self, k: K, v: V) -> Result<Option<V>, Error>
+    where
+        K: Eq + Hash,
+    {
+        self.insert_timeout(k, v, Duration::ZERO)
     }
-}
 
-impl<K, V> From<MutableShardMap<K, V>> for ShardMap<K, V> {
-    fn from(from: MutableShardMap<K, V>) -> Self {
-        Self {
-            shards: from
-                .shards
-                .into_iter()
-                .map(|v| v.into_inner().unwrap())
-                .collect(),
-            _phantom_data: PhantomData,
-        }
+    pub fn insert_timeout(&self, k: K, v: V, timeout: Duration) -> Result<Option<V>, Error>
+    where
+        K: Eq + Hash,
+    {
+        let hash = self.hash(&k);
+        let mut map = self.shards[self.shard(hash)].try_lock_with(self.poison_policy, timeout)?;
+        Ok(insert_hashed(&mut map, hash, k, v))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn remove_nonblocking<Q>(&self, k: &Q) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.remove_timeout(k, Duration::ZERO)
+    }
 
-    #[test]
-    fn test_shard_map() {
-        let map = MutableShardMap::<usize, usize>::default();
+    pub fn remove_timeout<Q>(&self, k: &Q, timeout: Duration) -> Result<Option<V>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let hash = self.hash(k);
+        let mut map = self.shards[self.shard(hash)].try_lock_with(self.poison_policy, timeout)?;
+        Ok(remove_hashed(&mut map, hash, k))
+    }
 
-        assert!(map.is_empty());
-        map.insert(1, 1);
-        assert_eq!(map.len(), 1);
-        map.clear();
-        assert!(map.is_empty());
-        assert_eq!(map.len(), 0);
+    pub fn insert_many<I>(&self, items: I)
+    where
+        K: Eq + Hash,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.try_insert_many(items).unwrap()
+    }
 
-        const N: usize = 1 << 15;
-        for i in 0..N {
-            assert!(map.insert(i, i * 2).is_none());
+    /// Shards handled before a poisoned one keep the entries inserted into
+    /// them when this fails.
+    pub fn try_insert_many<I>(&self, items: I) -> Result<(), Error>
+    where
+        K: Eq + Hash,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut buckets = (0..self.shards.len()).map(|_| vec![]).collect::<Vec<_>>();
+        for (k, v) in items {
+            let hash = self.hash(&k);
+            buckets[self.shard(hash)].push((hash, k, v));
         }
-        for i in 0..N {
-            if i % 2 == 0 {
-                assert_eq!(map.remove(&i), Some(i * 2));
-            } else {
-                assert_eq!(map.get_cloned(&i), Some(i * 2));
+        for (shard, bucket) in self.shards.iter().zip(buckets) {
+            if bucket.is_empty() {
+                continue;
+            }
+            let mut map = shard.lock_with(self.poison_policy)?;
+            map.reserve(bucket.len());
+            for (hash, k, v) in bucket {
+                insert_hashed(&mut map, hash, k, v);
             }
         }
-        assert!(!map.contains_key(&0));
-        assert!(map.contains_key(&1));
+        Ok(())
+    }
+
+    pub fn get_many_cloned<Q>(&self, keys: &[&Q]) -> Vec<Option<V>>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        V: Clone,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.try_get_many_cloned(keys).unwrap()
+    }
+
+    pub fn try_get_many_cloned<Q>(&self, keys: &[&Q]) -> Result<Vec<Option<V>>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        V: Clone,
+        Q: Eq + Hash + ?Sized,
+    {
+        let mut buckets = (0..self.shards.len()).map(|_| vec![]).collect::<Vec<_>>();
+        for (i, k) in keys.iter().enumerate() {
+            let hash = self.hash(*k);
+            buckets[self.shard(hash)].push((hash, i));
+        }
+        let mut values = vec![None; keys.len()];
+        for (shard, bucket) in self.shards.iter().zip(buckets) {
+            if bucket.is_empty() {
+                continue;
+            }
+            let map = shard.read_with(self.poison_policy)?;
+            for (hash, i) in bucket {
+                values[i] = map
+                    .raw_entry()
+                    .from_key_hashed_nocheck(hash, keys[i])
+                    .map(|(_, v)| v.clone());
+            }
+        }
+        Ok(values)
+    }
+
+    pub fn remove_many<'a, Q, I>(&self, keys: I) -> Vec<Option<V>>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        self.try_remove_many(keys).unwrap()
+    }
+
+    /// Shards handled before a poisoned one keep their removals when this
+    /// fails.
+    pub fn try_remove_many<'a, Q, I>(&self, keys: I) -> Result<Vec<Option<V>>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let mut buckets = (0..self.shards.len()).map(|_| vec![]).collect::<Vec<_>>();
+        let mut count = 0;
+        for (i, k) in keys.into_iter().enumerate() {
+            let hash = self.hash(k);
+            buckets[self.shard(hash)].push((hash, i, k));
+            count += 1;
+        }
+        let mut values = (0..count).map(|_| None).collect::<Vec<_>>();
+        for (shard, bucket) in self.shards.iter().zip(buckets) {
+            if bucket.is_empty() {
+                continue;
+            }
+            let mut map = shard.lock_with(self.poison_policy)?;
+            for (hash, i, k) in bucket {
+                values[i] = remove_hashed(&mut map, hash, k);
+            }
+        }
+        Ok(values)
+    }
+
+    pub fn entry(&self, k: K) -> Entry<K, V, T::Guard<'_>>
+    where
+        K: Eq + Hash,
+    {
+        self.try_entry(k).unwrap()
+    }
+
+    pub fn try_entry(&self, k: K) -> Result<Entry<K, V, T::Guard<'_>>, Error>
+    where
+        K: Eq + Hash,
+    {
+        let hash = self.hash(&k);
+        let map = self.shards[self.shard(hash)].lock_with(self.poison_policy)?;
+        Ok(Entry::new(map, hash, k))
+    }
+
+    pub fn update<Q, R, F>(&self, k: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        self.try_update(k, f).unwrap()
+    }
+
+    pub fn try_update<Q, R, F>(&self, k: &Q, f: F) -> Result<Option<R>, Error>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+        F: FnOnce(&mut V) -> R,
+    {
+        let hash = self.hash(k);
+        let mut map = self.shards[self.shard(hash)].lock_with(self.poison_policy)?;
+        Ok(match map.raw_entry_mut().from_key_hashed_nocheck(hash, k) {
+            RawEntryMut::Occupied(mut entry) => Some(f(entry.get_mut())),
+            RawEntryMut::Vacant(_) => None,
+        })
+    }
+
+    pub fn alter<F>(&self, k: K, f: F)
+    where
+        K: Eq + Hash,
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
+        self.try_alter(k, f).unwrap()
+    }
+
+    pub fn try_alter<F>(&self, k: K, f: F) -> Result<(), Error>
+    where
+        K: Eq + Hash,
+        F: FnOnce(Option<V>) -> Option<V>,
+    {
+        let hash = self.hash(&k);
+        let mut map = self.shards[self.shard(hash)].lock_with(self.poison_policy)?;
+        match map.raw_entry_mut().from_key_hashed_nocheck(hash, &k) {
+            RawEntryMut::Occupied(entry) => {
+                entry.replace_entry_with(|_, v| f(Some(v)));
+            }
+            RawEntryMut::Vacant(entry) => {
+                if let Some(v) = f(None) {
+                    entry.insert_hashed_nocheck(hash, k, v);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn upsert<F>(&self, k: K, default: V, f: F)
+    where
+        K: Eq + Hash,
+        F: FnOnce(&mut V),
+    {
+        self.try_upsert(k, default, f).unwrap()
+    }
+
+    pub fn try_upsert<F>(&self, k: K, default: V, f: F) -> Result<(), Error>
+    where
+        K: Eq + Hash,
+        F: FnOnce(&mut V),
+    {
+        let hash = self.hash(&k);
+        let mut map = self.shards[self.shard(hash)].lock_with(self.poison_policy)?;
+        match map.raw_entry_mut().from_key_hashed_nocheck(hash, &k) {
+            RawEntryMut::Occupied(mut entry) => f(entry.get_mut()),
+            RawEntryMut::Vacant(entry) => {
+                entry.insert_hashed_nocheck(hash, k, default);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&self) {
+        self.try_clear().unwrap()
+    }
+
+    pub fn try_clear(&self) -> Result<(), Error> {
+        for shard in &self.shards {
+            shard.clear(self.poison_policy)?;
+        }
+        Ok(())
+    }
+
+    /// Visits every entry, locking one shard at a time.
+    ///
+    /// Each shard is locked for the whole time its entries are visited, so
+    /// an entry is seen at most once and never in a half-written state.
+    /// Writers to other shards proceed concurrently: a write is observed if
+    /// it lands before its shard is visited and missed if it lands after, so
+    /// the walk is not a point-in-time view of the whole map. Calling back
+    /// into the map from `f` deadlocks if it touches the shard being visited.
+    pub fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        self.try_for_each(f).unwrap()
+    }
+
+    /// Shards visited before a poisoned one have already been passed to `f`
+    /// when this fails.
+    pub fn try_for_each<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&K, &V),
+    {
+        for shard in &self.shards {
+            let map = shard.read_with(self.poison_policy)?;
+            map.iter().for_each(|(k, v)| f(k, v));
+        }
+        Ok(())
+    }
+
+    /// Like [`ShardMap::for_each`], with mutable access to the values.
+    pub fn for_each_mut<F>(&self, f: F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        self.try_for_each_mut(f).unwrap()
+    }
+
+    pub fn try_for_each_mut<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&K, &mut V),
+    {
+        for shard in &self.shards {
+            let mut map = shard.lock_with(self.poison_policy)?;
+            map.iter_mut().for_each(|(k, v)| f(k, v));
+        }
+        Ok(())
+    }
+
+    /// Keeps the entries for which `f` returns true, with the same locking
+    /// and visibility as [`ShardMap::for_each`].
+    pub fn retain<F>(&self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.try_retain(f).unwrap()
+    }
+
+    pub fn try_retain<F>(&self, mut f: F) -> Result<(), Error>
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        for shard in &self.shards {
+            let mut map = shard.lock_with(self.poison_policy)?;
+            map.retain(|k, v| f(k, v));
+        }
+        Ok(())
+    }
+
+    /// Copies the map into an immutable one, cloning one shard at a time
+    /// under its lock. Writers may proceed in other shards meanwhile, with
+    /// the same visibility as [`ShardMap::for_each`].
+    pub fn snapshot(&self) -> ShardMap<K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.try_snapshot().unwrap()
+    }
+
+    pub fn try_snapshot(&self) -> Result<ShardMap<K, V, S>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        Ok(ShardMap {
+            shards: self
+                .shards
+                .iter()
+                .map(|s| Ok(s.read_with(self.poison_policy)?.clone()))
+                .collect::<Result<_, Error>>()?,
+            hash_builder: self.hash_builder.clone(),
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        })
+    }
+
+    /// Copies the map into an immutable one, locking every shard before
+    /// copying so the result is a point-in-time view. All writers are
+    /// blocked until the copy finishes.
+    pub fn snapshot_consistent(&self) -> ShardMap<K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.try_snapshot_consistent().unwrap()
+    }
+
+    pub fn try_snapshot_consistent(&self) -> Result<ShardMap<K, V, S>, Error>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let guards = self
+            .shards
+            .iter()
+            .map(|s| s.read_with(self.poison_policy))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ShardMap {
+            shards: guards.iter().map(|m| HashMap::clone(m)).collect(),
+            hash_builder: self.hash_builder.clone(),
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        })
+    }
+
+    /// Moves all entries out into an immutable map and leaves this map empty
+    /// and writable. Every shard is locked before any is emptied, so each
+    /// write lands either entirely before or entirely after the rotation.
+    pub fn rotate(&self) -> ShardMap<K, V, S> {
+        self.try_rotate().unwrap()
+    }
+
+    /// Nothing is moved out when this fails.
+    pub fn try_rotate(&self) -> Result<ShardMap<K, V, S>, Error> {
+        let mut guards = self
+            .shards
+            .iter()
+            .map(|s| s.lock_with(self.poison_policy))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ShardMap {
+            shards: guards
+                .iter_mut()
+                .map(|m| {
+                    let empty = HashMap::with_hasher(self.hash_builder.clone());
+                    std::mem::replace(&mut **m, empty)
+                })
+                .collect(),
+            hash_builder: self.hash_builder.clone(),
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+impl<K, V, S: BuildHasher> InnerMap<K, V, S> for HashMap<K, V, S> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        HashMap::with_capacity_and_hasher(capacity, hash_builder)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.raw_entry().from_key_hashed_nocheck(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        insert_hashed(self, hash, k, v)
+    }
+}
+
+impl<K, V, S: BuildHasher> ImmutableInnerMap<K, V, S> for HashMap<K, V, S> {
+    type Iter<'a>
+        = hashbrown::hash_map::Iter<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.iter()
+    }
+
+    fn get<Q>(&self, hash: u64, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.raw_entry()
+            .from_key_hashed_nocheck(hash, k)
+            .map(|(_, v)| v)
+    }
+}
+
+impl<K, V, S: BuildHasher> InnerMap<K, V, S> for Mutex<HashMap<K, V, S>> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Mutex::new(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.lock().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.lock().unwrap_or_else(PoisonError::into_inner);
+        map.raw_entry().from_key_hashed_nocheck(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        // owned bulk loads have the shard to themselves and recover it, as
+        // under `PoisonPolicy::Recover`.
+        self.clear_poison();
+        let map = self.get_mut().unwrap_or_else(PoisonError::into_inner);
+        insert_hashed(map, hash, k, v)
+    }
+}
+
+impl<K, V, S: BuildHasher> MutableInnerMap<K, V, S> for Mutex<HashMap<K, V, S>> {
+    type Guard<'a>
+        = MutexGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+    type ReadGuard<'a>
+        = MutexGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>> {
+        self.lock()
+    }
+
+    fn read(&self) -> LockResult<Self::ReadGuard<'_>> {
+        self.lock()
+    }
+
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>> {
+        self.try_lock()
+    }
+
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_lock()
+    }
+
+    fn clear_poison(&self) {
+        self.clear_poison()
+    }
+}
+
+impl<K, V, S: BuildHasher> InnerMap<K, V, S> for RwLock<HashMap<K, V, S>> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        RwLock::new(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.read().unwrap_or_else(PoisonError::into_inner).len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.read().unwrap_or_else(PoisonError::into_inner);
+        map.raw_entry().from_key_hashed_nocheck(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        // owned bulk loads have the shard to themselves and recover it, as
+        // under `PoisonPolicy::Recover`.
+        self.clear_poison();
+        let map = self.get_mut().unwrap_or_else(PoisonError::into_inner);
+        insert_hashed(map, hash, k, v)
+    }
+}
+
+impl<K, V, S: BuildHasher> MutableInnerMap<K, V, S> for RwLock<HashMap<K, V, S>> {
+    type Guard<'a>
+        = RwLockWriteGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>> {
+        self.write()
+    }
+
+    fn read(&self) -> LockResult<Self::ReadGuard<'_>> {
+        self.read()
+    }
+
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>> {
+        self.try_write()
+    }
+
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_read()
+    }
+
+    fn clear_poison(&self) {
+        self.clear_poison()
+    }
+}
+
+impl<K, V, S: BuildHasher> MutableShardMap<K, V, S> {
+    pub fn try_freeze(self) -> Result<ShardMap<K, V, S>, Error> {
+        Ok(ShardMap {
+            shards: self
+                .shards
+                .into_iter()
+                .map(|v| self.poison_policy.into_inner(v.into_inner()))
+                .collect::<Result<_, _>>()?,
+            hash_builder: self.hash_builder,
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+impl<K, V, S: BuildHasher> From<MutableShardMap<K, V, S>> for ShardMap<K, V, S> {
+    fn from(from: MutableShardMap<K, V, S>) -> Self {
+        from.try_freeze().unwrap()
+    }
+}
+
+impl<K, V, S: BuildHasher> RwShardMap<K, V, S> {
+    pub fn try_freeze(self) -> Result<ShardMap<K, V, S>, Error> {
+        Ok(ShardMap {
+            shards: self
+                .shards
+                .into_iter()
+                .map(|v| self.poison_policy.into_inner(v.into_inner()))
+                .collect::<Result<_, _>>()?,
+            hash_builder: self.hash_builder,
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+impl<K, V, S: BuildHasher> From<RwShardMap<K, V, S>> for ShardMap<K, V, S> {
+    fn from(from: RwShardMap<K, V, S>) -> Self {
+        from.try_freeze().unwrap()
+    }
+}
+
+impl<K, V, S: BuildHasher> From<ShardMap<K, V, S>> for MutableShardMap<K, V, S> {
+    fn from(from: ShardMap<K, V, S>) -> Self {
+        Self {
+            shards: from.shards.into_iter().map(Mutex::new).collect(),
+            hash_builder: from.hash_builder,
+            poison_policy: from.poison_policy,
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher> ShardMap<K, V, S> {
+    pub fn thaw(self) -> MutableShardMap<K, V, S> {
+        self.into()
+    }
+
+    pub(crate) fn map_shards<T: InnerMap<K, V, S>>(
+        self,
+        f: impl FnMut(HashMap<K, V, S>) -> T,
+    ) -> ShardMap<K, V, S, T> {
+        ShardMap {
+            shards: self.shards.into_iter().map(f).collect(),
+            hash_builder: self.hash_builder,
+            poison_policy: self.poison_policy,
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shard_map() {
+        let map = MutableShardMap::<usize, usize>::default();
+
+        assert!(map.is_empty());
+        map.insert(1, 1);
+        assert_eq!(map.len(), 1);
+        map.clear();
+        assert!(map.is_empty());
+        assert_eq!(map.len(), 0);
+
+        const N: usize = 1 << 15;
+        for i in 0..N {
+            assert!(map.insert(i, i * 2).is_none());
+        }
+        for i in 0..N {
+            if i % 2 == 0 {
+                assert_eq!(map.remove(&i), Some(i * 2));
+            } else {
+                assert_eq!(map.get_cloned(&i), Some(i * 2));
+            }
+        }
+        assert!(!map.contains_key(&0));
+        assert!(map.contains_key(&1));
 
         let immutable_map: ShardMap<usize, usize> = map.into();
         assert!(!immutable_map.is_empty());
@@ -272,4 +1384,534 @@ mod tests {
         }
         assert!(immutable_map.contains_key(&1));
     }
+
+    #[test]
+    fn test_with_hasher() {
+        use std::hash::{BuildHasherDefault, DefaultHasher};
+        type FixedState = BuildHasherDefault<DefaultHasher>;
+
+        let map = MutableShardMap::<String, usize, FixedState>::with_shard_amount_and_hasher(
+            3,
+            FixedState::default(),
+        );
+        assert_eq!(map.shards.len(), 3);
+        for i in 0..1024 {
+            assert!(map.insert(i.to_string(), i).is_none());
+        }
+        assert!(map.shards.iter().all(|m| !m.lock().unwrap().is_empty()));
+
+        let immutable_map: ShardMap<String, usize, FixedState> = map.into();
+        assert_eq!(immutable_map.len(), 1024);
+        for i in 0..1024 {
+            assert_eq!(immutable_map.get(i.to_string().as_str()), Some(&i));
+        }
+
+        let map = ShardMap::<usize, usize, FixedState>::default();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn test_with_capacity_and_shard_amount() {
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(1);
+        assert_eq!(map.shard_amount(), 1);
+
+        let map = MutableShardMap::<usize, usize>::with_capacity(1000);
+        assert_eq!(map.shard_amount(), default_shard_amount());
+        let capacity = 1000usize.div_ceil(default_shard_amount());
+        for shard in &map.shards {
+            assert!(shard.lock().unwrap().capacity() >= capacity);
+        }
+
+        let map = MutableShardMap::<usize, usize>::with_capacity_and_shard_amount(1000, 7);
+        assert_eq!(map.shard_amount(), 7);
+        for shard in &map.shards {
+            assert!(shard.lock().unwrap().capacity() >= 143);
+        }
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.len(), 1000);
+    }
+
+    #[test]
+    fn test_hash_once() {
+        use std::cell::Cell;
+        use std::hash::DefaultHasher;
+
+        thread_local! {
+            static BUILD_COUNT: Cell<usize> = const { Cell::new(0) };
+        }
+
+        #[derive(Clone, Default)]
+        struct CountingState;
+
+        impl BuildHasher for CountingState {
+            type Hasher = DefaultHasher;
+
+            fn build_hasher(&self) -> DefaultHasher {
+                BUILD_COUNT.set(BUILD_COUNT.get() + 1);
+                DefaultHasher::new()
+            }
+        }
+
+        let map = MutableShardMap::<String, usize, CountingState>::with_capacity_and_hasher(
+            1024,
+            CountingState,
+        );
+        let key = "key".to_string();
+
+        BUILD_COUNT.set(0);
+        map.insert(key.clone(), 1);
+        assert_eq!(BUILD_COUNT.get(), 1);
+
+        BUILD_COUNT.set(0);
+        assert_eq!(map.get_cloned(key.as_str()), Some(1));
+        assert!(map.contains_key(key.as_str()));
+        assert_eq!(map.remove(key.as_str()), Some(1));
+        assert_eq!(BUILD_COUNT.get(), 3);
+    }
+
+    #[test]
+    fn test_entry() {
+        let map = MutableShardMap::<String, usize>::new();
+
+        *map.entry("a".to_string()).or_insert(1) += 10;
+        assert_eq!(map.get_cloned("a"), Some(11));
+        *map.entry("a".to_string()).or_insert(1) += 10;
+        assert_eq!(map.get_cloned("a"), Some(21));
+
+        assert_eq!(*map.entry("b".to_string()).or_default(), 0);
+        assert_eq!(*map.entry("c".to_string()).or_insert_with(|| 3), 3);
+
+        let v = map
+            .entry("c".to_string())
+            .and_modify(|v| *v *= 2)
+            .or_insert(0);
+        assert_eq!(v.key(), "c");
+        assert_eq!(*v, 6);
+        drop(v);
+
+        match map.entry("a".to_string()) {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key(), "a");
+                assert_eq!(entry.insert(5), 21);
+                assert_eq!(entry.get(), &5);
+                assert_eq!(entry.remove_entry(), ("a".to_string(), 5));
+            }
+            Entry::Vacant(_) => unreachable!(),
+        }
+        match map.entry("a".to_string()) {
+            Entry::Occupied(_) => unreachable!(),
+            Entry::Vacant(entry) => {
+                assert_eq!(entry.key(), "a");
+                assert_eq!(*entry.insert(7), 7);
+            }
+        }
+        assert_eq!(map.len(), 3);
+
+        let counter = MutableShardMap::<usize, usize>::new();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for i in 0..1000 {
+                        *counter.entry(i % 10).or_default() += 1;
+                    }
+                });
+            }
+        });
+        for i in 0..10 {
+            assert_eq!(counter.get_cloned(&i), Some(400));
+        }
+    }
+
+    #[test]
+    fn test_update_alter_upsert() {
+        let map = MutableShardMap::<String, Vec<usize>>::new();
+
+        assert_eq!(map.update("a", |v| v.push(1)), None);
+        map.upsert("a".to_string(), vec![0], |v| v.push(1));
+        map.upsert("a".to_string(), vec![0], |v| v.push(1));
+        assert_eq!(map.update("a", |v| v.len()), Some(2));
+        assert_eq!(map.get_cloned("a"), Some(vec![0, 1]));
+
+        map.alter("b".to_string(), |v| {
+            assert!(v.is_none());
+            Some(vec![2])
+        });
+        map.alter("b".to_string(), |v| {
+            v.map(|mut v| {
+                v.push(3);
+                v
+            })
+        });
+        assert_eq!(map.get_cloned("b"), Some(vec![2, 3]));
+        map.alter("b".to_string(), |_| None);
+        assert!(!map.contains_key("b"));
+        map.alter("c".to_string(), |_| None);
+        assert!(!map.contains_key("c"));
+        assert_eq!(map.len(), 1);
+    }
+
+    #[test]
+    fn test_get_with_ref_mut() {
+        let map = MutableShardMap::<usize, Vec<usize>>::new();
+        map.insert(1, vec![1, 2, 3]);
+
+        assert_eq!(map.get_with(&1, |v| v.len()), Some(3));
+        assert_eq!(map.get_with(&2, |v| v.len()), None);
+
+        let r = map.get_ref(&1).unwrap();
+        assert_eq!(r.key(), &1);
+        assert_eq!(r.value(), &[1, 2, 3]);
+        assert_eq!(r.iter().sum::<usize>(), 6);
+        drop(r);
+        assert!(map.get_ref(&2).is_none());
+
+        map.get_mut(&1).unwrap().push(4);
+        assert_eq!(map.get_cloned(&1), Some(vec![1, 2, 3, 4]));
+        assert!(map.get_mut(&2).is_none());
+    }
+
+    #[test]
+    fn test_iter() {
+        const N: usize = 1000;
+        let map = MutableShardMap::<usize, usize>::new();
+        for i in 0..N {
+            map.insert(i, i * 2);
+        }
+        let map: ShardMap<usize, usize> = map.into();
+
+        let mut items = map.iter().map(|(&k, &v)| (k, v)).collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items, (0..N).map(|i| (i, i * 2)).collect::<Vec<_>>());
+
+        assert_eq!(map.keys().sum::<usize>(), N * (N - 1) / 2);
+        assert_eq!(map.values().sum::<usize>(), N * (N - 1));
+        assert_eq!((&map).into_iter().count(), N);
+
+        let mut count = 0;
+        for (k, v) in &map {
+            assert_eq!(*v, *k * 2);
+            count += 1;
+        }
+        assert_eq!(count, N);
+
+        let mut items = map.into_iter().collect::<Vec<_>>();
+        items.sort();
+        assert_eq!(items, (0..N).map(|i| (i, i * 2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_for_each_retain() {
+        const N: usize = 1000;
+        let map = MutableShardMap::<usize, usize>::new();
+        for i in 0..N {
+            map.insert(i, i);
+        }
+
+        let mut sum = 0;
+        map.for_each(|k, v| {
+            assert_eq!(k, v);
+            sum += v;
+        });
+        assert_eq!(sum, N * (N - 1) / 2);
+
+        map.for_each_mut(|k, v| *v = k * 2);
+        assert_eq!(map.get_cloned(&7), Some(14));
+
+        map.retain(|k, _| k % 2 == 0);
+        assert_eq!(map.len(), N / 2);
+        assert!(!map.contains_key(&7));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in N..2 * N {
+                    map.insert(i, i * 2);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..10 {
+                    map.for_each(|k, v| assert_eq!(*v, k * 2));
+                }
+            });
+        });
+        assert_eq!(map.len(), N / 2 + N);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        const N: usize = 1000;
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(8);
+        for i in 0..N {
+            map.insert(i, i);
+        }
+
+        let snapshot = map.snapshot();
+        map.insert(N, N);
+        map.remove(&0);
+        assert_eq!(snapshot.len(), N);
+        assert_eq!(snapshot.shard_amount(), 8);
+        assert_eq!(snapshot.get(&0), Some(&0));
+        assert_eq!(snapshot.get(&N), None);
+
+        let snapshot = map.snapshot_consistent();
+        assert_eq!(snapshot.len(), N);
+        assert_eq!(snapshot.get(&0), None);
+        assert_eq!(snapshot.get(&N), Some(&N));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for i in 2 * N..3 * N {
+                    map.insert(i, i);
+                }
+            });
+            s.spawn(|| {
+                for _ in 0..10 {
+                    let snapshot = map.snapshot_consistent();
+                    let inserted = (2 * N..3 * N)
+                        .take_while(|i| snapshot.contains_key(i))
+                        .count();
+                    assert_eq!(snapshot.len(), N + inserted);
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn test_rotate() {
+        const N: usize = 1000;
+        let map = MutableShardMap::<usize, usize>::new();
+        for i in 0..N {
+            map.insert(i, i);
+        }
+
+        let frozen = map.rotate();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.get(&1), Some(&1));
+        assert!(map.is_empty());
+        map.insert(1, 2);
+        assert_eq!(frozen.get(&1), Some(&1));
+        assert_eq!(map.get_cloned(&1), Some(2));
+
+        let map = MutableShardMap::<usize, usize>::new();
+        let total = std::thread::scope(|s| {
+            let writer = s.spawn(|| {
+                for i in 0..10 * N {
+                    map.insert(i, i);
+                }
+            });
+            let mut total = 0;
+            while !writer.is_finished() {
+                total += map.rotate().len();
+            }
+            total
+        });
+        assert_eq!(total + map.rotate().len(), 10 * N);
+    }
+
+    #[test]
+    fn test_thaw() {
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(8);
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        let frozen: ShardMap<usize, usize> = map.into();
+
+        let map = frozen.thaw();
+        assert_eq!(map.shard_amount(), 8);
+        assert_eq!(map.len(), 1000);
+        map.insert(1000, 1000);
+        map.remove(&0);
+
+        let frozen: ShardMap<usize, usize> = map.into();
+        let map: MutableShardMap<usize, usize> = frozen.into();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get_cloned(&0), None);
+        assert_eq!(map.get_cloned(&1000), Some(1000));
+    }
+
+    #[test]
+    fn test_rw_shard_map() {
+        const N: usize = 1000;
+        let map = RwShardMap::<usize, String>::new();
+        for i in 0..N {
+            assert!(map.insert(i, i.to_string()).is_none());
+        }
+        assert_eq!(map.len(), N);
+        assert_eq!(map.remove(&0), Some("0".to_string()));
+        assert!(!map.contains_key(&0));
+
+        let r1 = map.get_ref(&1).unwrap();
+        let r2 = map.get_ref(&1).unwrap();
+        assert_eq!(r1.as_str(), "1");
+        assert_eq!(r2.as_str(), "1");
+        drop((r1, r2));
+
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for i in 1..N {
+                        assert_eq!(map.get_with(&i, |v| v.len()), Some(i.to_string().len()));
+                    }
+                });
+            }
+            s.spawn(|| *map.entry(N).or_default() += "x");
+        });
+
+        let frozen: ShardMap<usize, String> = map.into();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.get(&N).map(String::as_str), Some("x"));
+    }
+
+    #[test]
+    fn test_poison_policy() {
+        fn poisoned() -> MutableShardMap<usize, usize> {
+            let map = MutableShardMap::<usize, usize>::with_shard_amount(1);
+            map.insert(1, 1);
+            std::thread::scope(|s| {
+                let result = s
+                    .spawn(|| {
+                        let _guard = map.get_mut(&1);
+                        panic!("poison the shard");
+                    })
+                    .join();
+                assert!(result.is_err());
+            });
+            map
+        }
+
+        let map = poisoned();
+        assert_eq!(map.poison_policy(), PoisonPolicy::Propagate);
+        assert_eq!(map.try_insert(2, 2), Err(Error::Poisoned));
+        assert_eq!(map.try_get_cloned(&1), Err(Error::Poisoned));
+        assert!(map.try_entry(1).is_err());
+        assert_eq!(map.try_clear(), Err(Error::Poisoned));
+        assert_eq!(map.try_len(), Err(Error::Poisoned));
+        assert_eq!(map.try_is_empty(), Err(Error::Poisoned));
+        assert_eq!(map.try_contains_key(&1), Err(Error::Poisoned));
+        assert_eq!(map.try_insert_many([(2, 2)]), Err(Error::Poisoned));
+        assert_eq!(map.try_get_many_cloned(&[&1]), Err(Error::Poisoned));
+        assert_eq!(map.try_remove_many([&1]), Err(Error::Poisoned));
+        assert_eq!(map.try_for_each(|_, _| {}), Err(Error::Poisoned));
+        assert_eq!(map.try_for_each_mut(|_, _| {}), Err(Error::Poisoned));
+        assert_eq!(map.try_retain(|_, _| true), Err(Error::Poisoned));
+        assert!(matches!(map.try_snapshot(), Err(Error::Poisoned)));
+        assert!(matches!(
+            map.try_snapshot_consistent(),
+            Err(Error::Poisoned)
+        ));
+        assert!(matches!(map.try_rotate(), Err(Error::Poisoned)));
+        assert_eq!(Error::Poisoned.to_string(), "shard lock poisoned");
+
+        let mut map = poisoned();
+        map.set_poison_policy(PoisonPolicy::Recover);
+        assert_eq!(map.get_cloned(&1), Some(1));
+        assert_eq!(map.try_insert(2, 2), Ok(None));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.try_snapshot().map(|m| m.len()), Ok(2));
+        let mut sum = 0;
+        assert_eq!(map.try_for_each(|_, v| sum += v), Ok(()));
+        assert_eq!(sum, 3);
+        assert_eq!(map.try_retain(|k, _| *k == 2), Ok(()));
+        assert_eq!(map.try_rotate().map(|m| m.len()), Ok(1));
+        assert!(map.is_empty());
+
+        let mut map = poisoned();
+        map.set_poison_policy(PoisonPolicy::Clear);
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.try_contains_key(&1), Ok(false));
+        assert_eq!(map.try_len(), Ok(0));
+        assert!(map.is_empty());
+        assert_eq!(map.get_cloned(&1), None);
+        assert_eq!(map.try_insert(2, 2), Ok(None));
+        assert_eq!(map.len(), 1);
+
+        let mut map = poisoned();
+        map.extend([(2, 2)]);
+        assert_eq!(map.try_get_cloned(&1), Ok(Some(1)));
+        assert_eq!(map.try_len(), Ok(2));
+
+        let map = poisoned();
+        assert!(matches!(map.try_freeze(), Err(Error::Poisoned)));
+
+        let mut map = poisoned();
+        map.set_poison_policy(PoisonPolicy::Clear);
+        let frozen: ShardMap<usize, usize> = map.into();
+        assert!(frozen.is_empty());
+    }
+
+    #[test]
+    fn test_nonblocking_and_timeout() {
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(1);
+        assert_eq!(map.insert_nonblocking(1, 1), Ok(None));
+
+        let guard = map.get_mut(&1).unwrap();
+        assert_eq!(map.insert_nonblocking(2, 2), Err(Error::WouldBlock));
+        assert_eq!(map.get_cloned_nonblocking(&1), Err(Error::WouldBlock));
+        assert_eq!(map.remove_nonblocking(&1), Err(Error::WouldBlock));
+        let timeout = Duration::from_millis(10);
+        let start = Instant::now();
+        assert_eq!(map.insert_timeout(2, 2, timeout), Err(Error::WouldBlock));
+        assert!(start.elapsed() >= timeout);
+        drop(guard);
+
+        assert_eq!(map.get_cloned_timeout(&1, timeout), Ok(Some(1)));
+        assert_eq!(map.insert_timeout(2, 2, timeout), Ok(None));
+        assert_eq!(map.remove_timeout(&2, timeout), Ok(Some(2)));
+
+        let map = RwShardMap::<usize, usize>::with_shard_amount(1);
+        map.insert(1, 1);
+        let guard = map.get_ref(&1).unwrap();
+        assert_eq!(map.get_cloned_nonblocking(&1), Ok(Some(1)));
+        assert_eq!(map.insert_nonblocking(2, 2), Err(Error::WouldBlock));
+        drop(guard);
+        assert_eq!(map.insert_nonblocking(2, 2), Ok(None));
+    }
+
+    #[test]
+    fn test_batch() {
+        const N: usize = 1000;
+        let map = MutableShardMap::<String, usize>::new();
+        map.insert_many((0..N).map(|i| (i.to_string(), i)));
+        assert_eq!(map.len(), N);
+        map.insert_many([("0".to_string(), 10)]);
+        map.insert_many([]);
+
+        let keys = ["0", "1", "missing", "999"];
+        assert_eq!(
+            map.get_many_cloned(&keys),
+            vec![Some(10), Some(1), None, Some(999)]
+        );
+
+        assert_eq!(
+            map.remove_many(["1", "missing", "1", "2"]),
+            vec![Some(1), None, None, Some(2)]
+        );
+        assert_eq!(map.len(), N - 2);
+        assert!(map.remove_many(Vec::<&str>::new()).is_empty());
+    }
+
+    #[test]
+    fn test_from_iter_extend() {
+        const N: usize = 1000;
+        let map = (0..N)
+            .map(|i| (i, i))
+            .collect::<MutableShardMap<usize, usize>>();
+        assert_eq!(map.len(), N);
+        (&map).extend((N..2 * N).map(|i| (i, i)));
+        assert_eq!(map.len(), 2 * N);
+        let mut map = map;
+        map.extend([(0, 1)]);
+        assert_eq!(map.get_cloned(&0), Some(1));
+
+        let mut frozen = (0..N).map(|i| (i, i)).collect::<ShardMap<usize, usize>>();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.get(&7), Some(&7));
+        frozen.extend([(0, 1), (N, N)]);
+        assert_eq!(frozen.len(), N + 1);
+        assert_eq!(frozen.get(&0), Some(&1));
+
+        let map = (0..N).map(|i| (i, i)).collect::<RwShardMap<usize, usize>>();
+        assert_eq!(map.len(), N);
+    }
 }
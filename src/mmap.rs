@@ -0,0 +1,290 @@
+use crate::codec::{checksum, invalid};
+use crate::{fmix64, shard_index, Codec, ShardMap};
+use memmap2::Mmap;
+use std::fs::File;
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Write};
+use std::marker::PhantomData;
+use std::path::Path;
+
+const MAGIC: [u8; 4] = *b"SHMM";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 4 + 4 + 8 + 8;
+const FOOTER_LEN: usize = 8;
+const DIRECTORY_ENTRY_LEN: usize = 32;
+const SLOT_LEN: usize = 16;
+
+// the map's own hasher may be seeded per process, so the file is indexed by
+// a fixed hash of the encoded key instead.
+fn key_hash(key: &[u8]) -> u64 {
+    fmix64(checksum(key))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let bytes = bytes.get(offset..offset.checked_add(8)?)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// reads a length-prefixed field and returns it with the offset after it.
+fn read_field(bytes: &[u8], offset: usize) -> Option<(&[u8], usize)> {
+    let len = usize::try_from(read_u64(bytes, offset)?).ok()?;
+    let start = offset + 8;
+    let end = start.checked_add(len)?;
+    Some((bytes.get(start..end)?, end))
+}
+
+#[derive(Clone, Copy)]
+struct Shard {
+    table_offset: usize,
+    slot_amount: usize,
+    data_offset: usize,
+    data_len: usize,
+}
+
+/// The layout written by [`ShardMap::write_mapped`] is a header, then one
+/// open-addressed table and one entry area per shard, then the shard
+/// directory and its offset.
+impl<K, V, S> ShardMap<K, V, S>
+where
+    K: Codec + Eq + Hash,
+    V: Codec,
+    S: BuildHasher + Clone,
+{
+    pub fn write_mapped(&self, mut writer: impl Write) -> io::Result<()> {
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&MAGIC);
+        VERSION.encode(&mut header);
+        self.shards.len().encode(&mut header);
+        self.len().encode(&mut header);
+        writer.write_all(&header)?;
+
+        let mut offset = HEADER_LEN;
+        let mut directory = Vec::with_capacity(self.shards.len() * DIRECTORY_ENTRY_LEN);
+        let (mut key, mut value, mut data) = (Vec::new(), Vec::new(), Vec::new());
+        // entries are re-bucketed by the file hash, not the map hash.
+        let mut buckets = vec![Vec::new(); self.shards.len()];
+        for (k, v) in self {
+            key.clear();
+            k.encode(&mut key);
+            buckets[shard_index(key_hash(&key), self.shards.len())].push((k, v));
+        }
+
+        for bucket in buckets {
+            let slot_amount = (bucket.len() * 2).next_power_of_two();
+            let mut table = vec![0u8; slot_amount * SLOT_LEN];
+            data.clear();
+            for (k, v) in bucket {
+                key.clear();
+                k.encode(&mut key);
+                value.clear();
+                v.encode(&mut value);
+
+                let hash = key_hash(&key);
+                let mut slot = hash as usize & (slot_amount - 1);
+                while table[slot * SLOT_LEN + 8..(slot + 1) * SLOT_LEN] != [0; 8] {
+                    slot = (slot + 1) & (slot_amount - 1);
+                }
+                let entry = &mut table[slot * SLOT_LEN..(slot + 1) * SLOT_LEN];
+                entry[..8].copy_from_slice(&hash.to_le_bytes());
+                // 0 marks an empty slot, so entry offsets are stored plus one.
+                entry[8..].copy_from_slice(&(data.len() as u64 + 1).to_le_bytes());
+
+                key.len().encode(&mut data);
+                data.extend_from_slice(&key);
+                value.len().encode(&mut data);
+                data.extend_from_slice(&value);
+            }
+            offset.encode(&mut directory);
+            slot_amount.encode(&mut directory);
+            (offset + table.len()).encode(&mut directory);
+            data.len().encode(&mut directory);
+            writer.write_all(&table)?;
+            writer.write_all(&data)?;
+            offset += table.len() + data.len();
+        }
+        writer.write_all(&directory)?;
+        offset.encode(&mut directory);
+        writer.write_all(&directory[directory.len() - FOOTER_LEN..])?;
+        writer.flush()
+    }
+}
+
+/// A read-only map served straight from a file written by
+/// [`ShardMap::write_mapped`].
+///
+/// Opening only checks the header and the shard directory; entries are found
+/// and decoded on lookup, so startup does not depend on the file size and
+/// the pages are shared by every process mapping the same file.
+pub struct MappedShardMap<K, V> {
+    mmap: Mmap,
+    shards: Vec<Shard>,
+    len: usize,
+    _phantom_data: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> MappedShardMap<K, V> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the file must not be truncated or modified while it is
+        // mapped; exports are written once and then only read.
+        let mmap = unsafe { Mmap::map(&file)? };
+        Self::from_mmap(mmap)
+    }
+
+    fn from_mmap(mmap: Mmap) -> io::Result<Self> {
+        let bytes = &mmap[..];
+        if bytes.len() < HEADER_LEN + FOOTER_LEN || bytes[..4] != MAGIC {
+            return Err(invalid("not a mapped shard map file"));
+        }
+        if bytes[4..8] != VERSION.to_le_bytes() {
+            return Err(invalid("unsupported mapped shard map file version"));
+        }
+        let to_usize = |v: Option<u64>| {
+            v.and_then(|v| usize::try_from(v).ok())
+                .ok_or_else(|| invalid("corrupted mapped shard map file"))
+        };
+        let shard_amount = to_usize(read_u64(bytes, 8))?;
+        let len = to_usize(read_u64(bytes, 16))?;
+        let directory_offset = to_usize(read_u64(bytes, bytes.len() - FOOTER_LEN))?;
+        let directory_end = shard_amount
+            .checked_mul(DIRECTORY_ENTRY_LEN)
+            .and_then(|l| l.checked_add(directory_offset));
+        if shard_amount == 0 || directory_end != Some(bytes.len() - FOOTER_LEN) {
+            return Err(invalid("corrupted shard directory"));
+        }
+
+        let shards = (0..shard_amount)
+            .map(|idx| {
+                let entry = directory_offset + idx * DIRECTORY_ENTRY_LEN;
+                let shard = Shard {
+                    table_offset: to_usize(read_u64(bytes, entry))?,
+                    slot_amount: to_usize(read_u64(bytes, entry + 8))?,
+                    data_offset: to_usize(read_u64(bytes, entry + 16))?,
+                    data_len: to_usize(read_u64(bytes, entry + 24))?,
+                };
+                let table_end = shard
+                    .slot_amount
+                    .checked_mul(SLOT_LEN)
+                    .and_then(|l| l.checked_add(shard.table_offset));
+                let data_end = shard.data_offset.checked_add(shard.data_len);
+                if !shard.slot_amount.is_power_of_two()
+                    || table_end.is_none_or(|end| end > directory_offset)
+                    || data_end.is_none_or(|end| end > directory_offset)
+                {
+                    return Err(invalid("corrupted shard directory"));
+                }
+                Ok(shard)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            mmap,
+            shards,
+            len,
+            _phantom_data: PhantomData,
+        })
+    }
+
+    pub fn shard_amount(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Looks up an encoded key and returns the encoded value, borrowed from
+    /// the mapping.
+    pub fn get_raw(&self, key: &[u8]) -> Option<&[u8]> {
+        let bytes = &self.mmap[..];
+        let hash = key_hash(key);
+        let shard = self.shards[shard_index(hash, self.shards.len())];
+        let data = &bytes[shard.data_offset..shard.data_offset + shard.data_len];
+        let mask = shard.slot_amount - 1;
+        let mut slot = hash as usize & mask;
+        for _ in 0..shard.slot_amount {
+            let offset = shard.table_offset + slot * SLOT_LEN;
+            let entry = usize::try_from(read_u64(bytes, offset + 8)?).ok()?;
+            if entry == 0 {
+                return None;
+            }
+            if read_u64(bytes, offset)? == hash {
+                let (k, end) = read_field(data, entry - 1)?;
+                if k == key {
+                    return Some(read_field(data, end)?.0);
+                }
+            }
+            slot = (slot + 1) & mask;
+        }
+        None
+    }
+
+    pub fn contains_key(&self, k: &K) -> bool
+    where
+        K: Codec,
+    {
+        let mut key = Vec::new();
+        k.encode(&mut key);
+        self.get_raw(&key).is_some()
+    }
+
+    pub fn get(&self, k: &K) -> io::Result<Option<V>>
+    where
+        K: Codec,
+        V: Codec,
+    {
+        let mut key = Vec::new();
+        k.encode(&mut key);
+        self.get_raw(&key)
+            .map(|mut v| V::decode(&mut v))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Codec, MappedShardMap, ShardMap};
+    use std::io;
+
+    #[test]
+    fn test_mapped_shard_map() {
+        const N: usize = 10000;
+        let map = (0..N)
+            .map(|i| (i.to_string(), i as u64))
+            .collect::<ShardMap<String, u64>>();
+        let path = std::env::temp_dir().join(format!("shardmap-{}.mmap", std::process::id()));
+        map.write_mapped(io::BufWriter::new(std::fs::File::create(&path).unwrap()))
+            .unwrap();
+
+        let mapped = MappedShardMap::<String, u64>::open(&path).unwrap();
+        assert_eq!(mapped.len(), N);
+        assert_eq!(mapped.shard_amount(), map.shard_amount());
+        for i in 0..N {
+            assert_eq!(mapped.get(&i.to_string()).unwrap(), Some(i as u64));
+        }
+        assert!(mapped.contains_key(&"7".to_string()));
+        assert!(!mapped.contains_key(&N.to_string()));
+        assert_eq!(mapped.get(&"-1".to_string()).unwrap(), None);
+
+        let mut key = Vec::new();
+        "42".to_string().encode(&mut key);
+        assert_eq!(mapped.get_raw(&key), Some(&42u64.to_le_bytes()[..]));
+
+        let empty = ShardMap::<String, u64>::with_shard_amount(3);
+        empty
+            .write_mapped(std::fs::File::create(&path).unwrap())
+            .unwrap();
+        let mapped = MappedShardMap::<String, u64>::open(&path).unwrap();
+        assert!(mapped.is_empty());
+        assert!(!mapped.contains_key(&"7".to_string()));
+
+        std::fs::write(&path, b"SHMP").unwrap();
+        let err = MappedShardMap::<String, u64>::open(&path).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{insert_hashed, HashMap, InnerMap, MutableInnerMap, ShardMap};
+use ::parking_lot::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::marker::PhantomData;
+use std::sync::{LockResult, TryLockError, TryLockResult};
+use std::time::Duration;
+
+pub type ParkingLotShardMap<K, V, S = RandomState> = ShardMap<K, V, S, Mutex<HashMap<K, V, S>>>;
+pub type ParkingLotRwShardMap<K, V, S = RandomState> = ShardMap<K, V, S, RwLock<HashMap<K, V, S>>>;
+
+impl<K, V, S: BuildHasher> InnerMap<K, V, S> for Mutex<HashMap<K, V, S>> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Mutex::new(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.lock();
+        map.raw_entry().from_key_hashed_nocheck(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        insert_hashed(self.get_mut(), hash, k, v)
+    }
+}
+
+impl<K, V, S: BuildHasher> MutableInnerMap<K, V, S> for Mutex<HashMap<K, V, S>> {
+    type Guard<'a>
+        = MutexGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+    type ReadGuard<'a>
+        = MutexGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>> {
+        Ok(self.lock())
+    }
+
+    fn read(&self) -> LockResult<Self::ReadGuard<'_>> {
+        Ok(self.lock())
+    }
+
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>> {
+        self.try_lock().ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_lock().ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_lock_for(&self, timeout: Duration) -> TryLockResult<Self::Guard<'_>> {
+        self.try_lock_for(timeout).ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_read_for(&self, timeout: Duration) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_lock_for(timeout).ok_or(TryLockError::WouldBlock)
+    }
+}
+
+impl<K, V, S: BuildHasher> InnerMap<K, V, S> for RwLock<HashMap<K, V, S>> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        RwLock::new(HashMap::with_capacity_and_hasher(capacity, hash_builder))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        let map = self.read();
+        map.raw_entry().from_key_hashed_nocheck(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        insert_hashed(self.get_mut(), hash, k, v)
+    }
+}
+
+impl<K, V, S: BuildHasher> MutableInnerMap<K, V, S> for RwLock<HashMap<K, V, S>> {
+    type Guard<'a>
+        = RwLockWriteGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+    type ReadGuard<'a>
+        = RwLockReadGuard<'a, HashMap<K, V, S>>
+    where
+        Self: 'a;
+
+    fn lock(&self) -> LockResult<Self::Guard<'_>> {
+        Ok(self.write())
+    }
+
+    fn read(&self) -> LockResult<Self::ReadGuard<'_>> {
+        Ok(self.read())
+    }
+
+    fn try_lock(&self) -> TryLockResult<Self::Guard<'_>> {
+        self.try_write().ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_read(&self) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_read().ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_lock_for(&self, timeout: Duration) -> TryLockResult<Self::Guard<'_>> {
+        self.try_write_for(timeout).ok_or(TryLockError::WouldBlock)
+    }
+
+    fn try_read_for(&self, timeout: Duration) -> TryLockResult<Self::ReadGuard<'_>> {
+        self.try_read_for(timeout).ok_or(TryLockError::WouldBlock)
+    }
+}
+
+impl<K, V, S: BuildHasher> From<ParkingLotShardMap<K, V, S>> for ShardMap<K, V, S> {
+    fn from(from: ParkingLotShardMap<K, V, S>) -> Self {
+        Self {
+            shards: from.shards.into_iter().map(Mutex::into_inner).collect(),
+            hash_builder: from.hash_builder,
+            poison_policy: from.poison_policy,
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+impl<K, V, S: BuildHasher> From<ParkingLotRwShardMap<K, V, S>> for ShardMap<K, V, S> {
+    fn from(from: ParkingLotRwShardMap<K, V, S>) -> Self {
+        Self {
+            shards: from.shards.into_iter().map(RwLock::into_inner).collect(),
+            hash_builder: from.hash_builder,
+            poison_policy: from.poison_policy,
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parking_lot_shard_map() {
+        const N: usize = 1000;
+        let map = ParkingLotShardMap::<usize, usize>::with_shard_amount(1);
+        let rw_map = ParkingLotRwShardMap::<usize, usize>::new();
+        std::thread::scope(|s| {
+            for t in 0..4 {
+                let (map, rw_map) = (&map, &rw_map);
+                s.spawn(move || {
+                    for i in (t..N).step_by(4) {
+                        assert!(map.insert(i, i).is_none());
+                        *rw_map.entry(i % 10).or_default() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(map.len(), N);
+        assert_eq!(rw_map.len(), 10);
+        assert_eq!(map.remove(&0), Some(0));
+        assert_eq!(*map.get_ref(&1).unwrap(), 1);
+        *map.get_mut(&1).unwrap() += 1;
+        assert_eq!(map.get_cloned(&1), Some(2));
+        assert_eq!(rw_map.get_with(&0, |v| *v), Some(N / 10));
+
+        let guard = map.get_mut(&1).unwrap();
+        assert_eq!(map.insert_nonblocking(2, 2), Err(crate::Error::WouldBlock));
+        let timeout = Duration::from_millis(10);
+        assert_eq!(
+            map.remove_timeout(&1, timeout),
+            Err(crate::Error::WouldBlock)
+        );
+        drop(guard);
+        assert_eq!(map.remove_timeout(&1, timeout), Ok(Some(2)));
+
+        let frozen: ShardMap<usize, usize> = map.into();
+        assert_eq!(frozen.len(), N - 2);
+        let frozen: ShardMap<usize, usize> = rw_map.into();
+        assert_eq!(frozen.values().sum::<usize>(), N);
+    }
+}
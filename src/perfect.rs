@@ -0,0 +1,344 @@
+use crate::{
+    fmix64, insert_hashed, HashMap, ImmutableInnerMap, InnerMap, MutableShardMap, ShardMap,
+};
+use std::borrow::Borrow;
+use std::cmp::Reverse;
+use std::hash::{BuildHasher, Hash};
+use std::iter::{Chain, Map};
+use std::{mem, slice, vec};
+
+// average keys per bucket; more buckets place faster but cost more pilots.
+const BUCKET_SIZE: usize = 4;
+// buckets without a pilot below this bound go to the overflow table.
+const MAX_PILOT: u32 = 1 << 20;
+const PILOT_SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+
+#[inline(always)]
+fn reduce(h: u64, n: usize) -> usize {
+    ((h as u128 * n as u128) >> 64) as usize
+}
+
+// the pilot is mixed in before the final scramble, otherwise keys of a bucket
+// that agree on their high bits would collide under every pilot.
+#[inline(always)]
+fn position(hash: u64, pilot: u32, n: usize) -> usize {
+    reduce(
+        fmix64(hash ^ (pilot as u64 + 1).wrapping_mul(PILOT_SEED)),
+        n,
+    )
+}
+
+// finds a pilot for every bucket and returns the pilots with the slot of
+// every hash, or the indices of the hashes in buckets that found none.
+fn search(hashes: &[u64]) -> Result<(Vec<u32>, Vec<usize>), Vec<usize>> {
+    let n = hashes.len();
+    let mut pilots = vec![0; n.div_ceil(BUCKET_SIZE)];
+    let mut buckets = vec![Vec::new(); pilots.len()];
+    for (idx, &hash) in hashes.iter().enumerate() {
+        buckets[reduce(fmix64(hash), pilots.len())].push(idx);
+    }
+    let mut order = (0..buckets.len()).collect::<Vec<_>>();
+    order.sort_unstable_by_key(|&b| Reverse(buckets[b].len()));
+
+    let mut taken = vec![false; n];
+    let mut slot_of = vec![0; n];
+    let mut spilled = Vec::new();
+    let mut positions = Vec::new();
+    for b in order {
+        let bucket = &buckets[b];
+        if bucket.is_empty() {
+            break;
+        }
+        // keys sharing a full hash can never be told apart by a pilot.
+        let distinct = bucket
+            .iter()
+            .enumerate()
+            .all(|(i, &x)| bucket[..i].iter().all(|&y| hashes[x] != hashes[y]));
+        let pilot = (0..MAX_PILOT).take_while(|_| distinct).find(|&pilot| {
+            positions.clear();
+            for &idx in bucket {
+                let pos = position(hashes[idx], pilot, n);
+                if taken[pos] || positions.contains(&pos) {
+                    return false;
+                }
+                positions.push(pos);
+            }
+            true
+        });
+        match pilot {
+            Some(pilot) => {
+                pilots[b] = pilot;
+                for (&idx, &pos) in bucket.iter().zip(&positions) {
+                    taken[pos] = true;
+                    slot_of[idx] = pos;
+                }
+            }
+            None => spilled.extend_from_slice(bucket),
+        }
+    }
+    if spilled.is_empty() {
+        Ok((pilots, slot_of))
+    } else {
+        Err(spilled)
+    }
+}
+
+/// An immutable shard indexed by a minimal perfect hash.
+///
+/// Keys are split into buckets, and each bucket gets a pilot that sends all
+/// of its keys to distinct slots, so a lookup is one hash and one probe.
+/// Keys inserted after the shard was built live in a plain overflow table
+/// until it outgrows the indexed part and everything is rebuilt.
+///
+/// Slots hold the entries densely, so an indexed entry costs its `(K, V)`
+/// pair plus a 4-byte pilot per bucket of four keys: 17 bytes for
+/// `(usize, usize)`, where a hash table spends 17 bytes per bucket and keeps
+/// at least an eighth of its buckets free.
+pub struct PerfectHashShard<K, V, S> {
+    pilots: Vec<u32>,
+    slots: Vec<(K, V)>,
+    overflow: HashMap<K, V, S>,
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Clone> PerfectHashShard<K, V, S> {
+    // buckets without a pilot would leave holes in the slots, so they move to
+    // the overflow table and the rest is placed again until none is left.
+    fn build(mut entries: Vec<(u64, K, V)>, hash_builder: S) -> Self {
+        let mut overflow = HashMap::with_hasher(hash_builder);
+        loop {
+            let hashes = entries.iter().map(|&(hash, _, _)| hash).collect::<Vec<_>>();
+            match search(&hashes) {
+                Ok((pilots, slot_of)) => {
+                    let mut placed = entries.into_iter().zip(slot_of).collect::<Vec<_>>();
+                    placed.sort_unstable_by_key(|&(_, pos)| pos);
+                    let mut slots = Vec::with_capacity(placed.len());
+                    slots.extend(placed.into_iter().map(|((_, k, v), _)| (k, v)));
+                    return Self {
+                        pilots,
+                        slots,
+                        overflow,
+                    };
+                }
+                Err(mut spilled) => {
+                    // removing from the back keeps the remaining indices valid.
+                    spilled.sort_unstable();
+                    overflow.reserve(spilled.len());
+                    for idx in spilled.into_iter().rev() {
+                        let (hash, k, v) = entries.swap_remove(idx);
+                        insert_hashed(&mut overflow, hash, k, v);
+                    }
+                }
+            }
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let hash_builder = self.overflow.hasher().clone();
+        let overflow = mem::replace(
+            &mut self.overflow,
+            HashMap::with_hasher(hash_builder.clone()),
+        );
+        let entries = mem::take(&mut self.slots)
+            .into_iter()
+            .chain(overflow)
+            .map(|(k, v)| (hash_builder.hash_one(&k), k, v))
+            .collect();
+        *self = Self::build(entries, hash_builder);
+    }
+}
+
+impl<K, V, S> PerfectHashShard<K, V, S> {
+    #[inline(always)]
+    fn slot(&self, hash: u64) -> Option<&(K, V)> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let pilot = self.pilots[reduce(fmix64(hash), self.pilots.len())];
+        Some(&self.slots[position(hash, pilot, self.slots.len())])
+    }
+
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        match self.slot(hash) {
+            Some((key, v)) if key.borrow() == k => Some(v),
+            _ if self.overflow.is_empty() => None,
+            _ => self
+                .overflow
+                .raw_entry()
+                .from_key_hashed_nocheck(hash, k)
+                .map(|(_, v)| v),
+        }
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Clone> From<HashMap<K, V, S>> for PerfectHashShard<K, V, S> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        let hash_builder = map.hasher().clone();
+        let entries = map
+            .into_iter()
+            .map(|(k, v)| (hash_builder.hash_one(&k), k, v))
+            .collect();
+        Self::build(entries, hash_builder)
+    }
+}
+
+impl<K, V, S: BuildHasher + Clone> InnerMap<K, V, S> for PerfectHashShard<K, V, S> {
+    fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self {
+            pilots: Vec::new(),
+            slots: Vec::new(),
+            overflow: HashMap::with_capacity_and_hasher(capacity, hash_builder),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.slots.is_empty() && self.overflow.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.slots.len() + self.overflow.len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        if !self.slots.is_empty() {
+            let pilot = self.pilots[reduce(fmix64(hash), self.pilots.len())];
+            let pos = position(hash, pilot, self.slots.len());
+            let (key, value) = &mut self.slots[pos];
+            if *key == k {
+                return Some(mem::replace(value, v));
+            }
+        }
+        let old = insert_hashed(&mut self.overflow, hash, k, v);
+        if self.overflow.len() > self.slots.len().max(BUCKET_SIZE * BUCKET_SIZE) {
+            self.rebuild();
+        }
+        old
+    }
+}
+
+fn slot_entry<K, V>(slot: &(K, V)) -> (&K, &V) {
+    (&slot.0, &slot.1)
+}
+
+type SlotIter<'a, K, V> = Map<slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+impl<K, V, S: BuildHasher + Clone> ImmutableInnerMap<K, V, S> for PerfectHashShard<K, V, S> {
+    type Iter<'a>
+        = Chain<SlotIter<'a, K, V>, hashbrown::hash_map::Iter<'a, K, V>>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.slots
+            .iter()
+            .map(slot_entry as _)
+            .chain(self.overflow.iter())
+    }
+
+    fn get<Q>(&self, hash: u64, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(hash, k)
+    }
+}
+
+impl<K, V, S> IntoIterator for PerfectHashShard<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = Chain<vec::IntoIter<(K, V)>, hashbrown::hash_map::IntoIter<K, V>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.slots.into_iter().chain(self.overflow)
+    }
+}
+
+impl<K: Eq + Hash, V, S: BuildHasher + Clone> MutableShardMap<K, V, S> {
+    /// Freezes the map with every shard rebuilt as a [`PerfectHashShard`].
+    pub fn freeze_perfect(self) -> ShardMap<K, V, S, PerfectHashShard<K, V, S>> {
+        ShardMap::from(self).map_shards(PerfectHashShard::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InnerMap, MutableShardMap, PerfectHashShard, ShardMap};
+    use std::hash::{BuildHasher, RandomState};
+
+    #[test]
+    fn test_perfect_hash_shard() {
+        const N: usize = 1 << 15;
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(8);
+        for i in 0..N {
+            map.insert(i, i * 2);
+        }
+        let frozen = map.freeze_perfect();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.shard_amount(), 8);
+        for i in 0..N {
+            assert_eq!(frozen.get(&i), Some(&(i * 2)));
+        }
+        assert!(frozen.contains_key(&7));
+        assert!(!frozen.contains_key(&N));
+        assert_eq!(frozen.get(&(N + 1)), None);
+        assert!(frozen.shards.iter().all(|s| s.overflow.is_empty()));
+        let bytes = frozen
+            .shards
+            .iter()
+            .map(|s| s.slots.capacity() * size_of::<(usize, usize)>() + s.pilots.capacity() * 4)
+            .sum::<usize>();
+        assert!(bytes <= N * 17 + 8 * 4, "{bytes} bytes");
+
+        let mut keys = frozen.keys().copied().collect::<Vec<_>>();
+        keys.sort_unstable();
+        assert!(keys.into_iter().eq(0..N));
+        let mut entries = frozen.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert!(entries.into_iter().eq((0..N).map(|i| (i, i * 2))));
+
+        let map = (0..N).map(|i| (i.to_string(), i)).collect::<ShardMap<
+            String,
+            usize,
+            RandomState,
+            PerfectHashShard<_, _, _>,
+        >>();
+        assert_eq!(map.len(), N);
+        for i in 0..N {
+            assert_eq!(map.get(i.to_string().as_str()), Some(&i));
+        }
+
+        // the bucket of keys sharing a full hash ends up in the overflow table.
+        let hash_builder = RandomState::new();
+        let entries = (0..100usize)
+            .map(|i| (hash_builder.hash_one(i), i, i))
+            .chain([(0, 1000, 0), (0, 1001, 1)])
+            .collect();
+        let mut shard = PerfectHashShard::build(entries, hash_builder.clone());
+        assert_eq!(shard.slots.len() + shard.overflow.len(), 102);
+        assert!(shard.overflow.keys().filter(|&&k| k >= 1000).count() == 2);
+        assert_eq!(shard.find(0, &1001), Some(&1));
+        assert_eq!(
+            shard.insert_mut(hash_builder.hash_one(7usize), 7, 8),
+            Some(7)
+        );
+        assert_eq!(shard.find(hash_builder.hash_one(7usize), &7), Some(&8));
+        assert_eq!(shard.find(hash_builder.hash_one(100usize), &100), None);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::codec::{count_mismatch, misplaced, Header, Section};
+use crate::{
+    default_shard_amount, insert_hashed, shard_index, Codec, HashMap, ImmutableInnerMap, InnerMap,
+    MutableInnerMap, PoisonPolicy, ShardMap,
+};
+use ::rayon::iter::plumbing::UnindexedConsumer;
+use ::rayon::iter::FlatMapIter;
+use ::rayon::prelude::*;
+use ::rayon::{slice, vec};
+use std::hash::{BuildHasher, Hash};
+use std::io::{self, Read};
+use std::marker::PhantomData;
+use std::sync::mpsc;
+
+type ShardParIter<'a, T, I> = FlatMapIter<slice::Iter<'a, T>, fn(&'a T) -> I>;
+
+impl<K, V, S, T> ParallelExtend<(K, V)> for &ShardMap<K, V, S, T>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Clone + Sync,
+    T: MutableInnerMap<K, V, S> + Sync,
+{
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        let map = *self;
+        par_iter
+            .into_par_iter()
+            .fold(Vec::new, |mut batch, item| {
+                batch.push(item);
+                batch
+            })
+            .for_each(|batch| map.insert_many(batch));
+    }
+}
+
+impl<K, V, S, T> ParallelExtend<(K, V)> for ShardMap<K, V, S, T>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Clone + Sync,
+    T: MutableInnerMap<K, V, S> + Sync,
+{
+    fn par_extend<I: IntoParallelIterator<Item = (K, V)>>(&mut self, par_iter: I) {
+        (&*self).par_extend(par_iter);
+    }
+}
+
+impl<K, V, S, T> FromParallelIterator<(K, V)> for ShardMap<K, V, S, T>
+where
+    K: Eq + Hash + Send,
+    V: Send,
+    S: BuildHasher + Clone + Default + Send + Sync,
+    T: InnerMap<K, V, S> + Send,
+{
+    fn from_par_iter<I: IntoParallelIterator<Item = (K, V)>>(par_iter: I) -> Self {
+        let hash_builder = S::default();
+        let shard_amount = default_shard_amount();
+        let new_buckets = || (0..shard_amount).map(|_| vec![]).collect::<Vec<_>>();
+
+        let chunks = par_iter
+            .into_par_iter()
+            .fold(new_buckets, |mut buckets, (k, v)| {
+                let hash = hash_builder.hash_one(&k);
+                buckets[shard_index(hash, shard_amount)].push((hash, k, v));
+                buckets
+            })
+            .collect::<Vec<_>>();
+
+        let mut parts = (0..shard_amount).map(|_| vec![]).collect::<Vec<_>>();
+        for chunk in chunks {
+            for (part, bucket) in parts.iter_mut().zip(chunk) {
+                part.push(bucket);
+            }
+        }
+
+        let shards = parts
+            .into_par_iter()
+            .map(|buckets| {
+                let capacity = buckets.iter().map(Vec::len).sum();
+                let mut shard = T::with_capacity_and_hasher(capacity, hash_builder.clone());
+                shard.extend_mut(buckets.into_iter().flatten());
+                shard
+            })
+            .collect();
+
+        Self {
+            shards,
+            hash_builder,
+            poison_policy: PoisonPolicy::default(),
+            _phantom_data: PhantomData,
+        }
+    }
+}
+
+pub struct ParIter<'a, K: 'a, V: 'a, S, T: ImmutableInnerMap<K, V, S> + 'a> {
+    inner: ShardParIter<'a, T, T::Iter<'a>>,
+}
+
+impl<'a, K, V, S, T> ParallelIterator for ParIter<'a, K, V, S, T>
+where
+    K: Sync,
+    V: Sync,
+    T: ImmutableInnerMap<K, V, S> + Sync,
+{
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.inner.drive_unindexed(consumer)
+    }
+}
+
+pub struct ParIntoIter<T: IntoIterator + Send> {
+    inner: FlatMapIter<vec::IntoIter<T>, fn(T) -> T>,
+}
+
+impl<T> ParallelIterator for ParIntoIter<T>
+where
+    T: IntoIterator + Send,
+    T::Item: Send,
+{
+    type Item = T::Item;
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.inner.drive_unindexed(consumer)
+    }
+}
+
+impl<K, V, S, T> ShardMap<K, V, S, T>
+where
+    K: Sync,
+    V: Sync,
+    T: ImmutableInnerMap<K, V, S> + Sync,
+{
+    pub fn par_iter(&self) -> ParIter<'_, K, V, S, T> {
+        let f: fn(&T) -> T::Iter<'_> = T::iter;
+        ParIter {
+            inner: self.shards.par_iter().flat_map_iter(f),
+        }
+    }
+
+    pub fn par_keys(&self) -> impl ParallelIterator<Item = &K> {
+        self.par_iter().map(|(k, _)| k)
+    }
+
+    pub fn par_values(&self) -> impl ParallelIterator<Item = &V> {
+        self.par_iter().map(|(_, v)| v)
+    }
+}
+
+impl<'a, K, V, S, T> IntoParallelIterator for &'a ShardMap<K, V, S, T>
+where
+    K: Sync,
+    V: Sync,
+    T: ImmutableInnerMap<K, V, S> + Sync,
+{
+    type Item = (&'a K, &'a V);
+    type Iter = ParIter<'a, K, V, S, T>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<K, V, S, T> IntoParallelIterator for ShardMap<K, V, S, T>
+where
+    K: Send,
+    V: Send,
+    T: ImmutableInnerMap<K, V, S> + IntoIterator<Item = (K, V)> + Send,
+{
+    type Item = (K, V);
+    type Iter = ParIntoIter<T>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        let f: fn(T) -> T = std::convert::identity;
+        ParIntoIter {
+            inner: self.shards.into_par_iter().flat_map_iter(f),
+        }
+    }
+}
+
+impl<K, V, S> ShardMap<K, V, S>
+where
+    K: Codec + Eq + Hash + Send,
+    V: Codec + Send,
+    S: BuildHasher + Clone + Send + Sync,
+{
+    pub fn par_read_from(reader: impl Read) -> io::Result<Self>
+    where
+        S: Default,
+    {
+        Self::par_read_from_with_hasher(reader, S::default())
+    }
+
+    /// Like [`ShardMap::read_from_with_hasher`], but each section is decoded
+    /// on the thread pool while the next one is being read.
+    pub fn par_read_from_with_hasher(mut reader: impl Read, hash_builder: S) -> io::Result<Self> {
+        let header = Header::read(&mut reader)?;
+        let direct = header.direct(&hash_builder);
+        let shard_amount = header.shard_amount;
+        let (sender, receiver) = mpsc::channel();
+        let mut count = 0usize;
+        ::rayon::in_place_scope(|scope| {
+            let hash_builder = &hash_builder;
+            for idx in 0..shard_amount {
+                let section = Section::read(&mut reader)?;
+                count = count.checked_add(section.len).ok_or_else(count_mismatch)?;
+                let sender = sender.clone();
+                scope.spawn(move |_| {
+                    let mut entries = Vec::with_capacity(section.capacity());
+                    let result = section.decode(|k, v| {
+                        let hash = hash_builder.hash_one(&k);
+                        if direct && shard_index(hash, shard_amount) != idx {
+                            return Err(misplaced());
+                        }
+                        entries.push((hash, k, v));
+                        Ok(())
+                    });
+                    let _ = sender.send((idx, result.map(|()| entries)));
+                });
+            }
+            Ok::<_, io::Error>(())
+        })?;
+        if count != header.len {
+            return Err(count_mismatch());
+        }
+
+        // every section was read at this point, so the shard amount is
+        // backed by the input.
+        drop(sender);
+        let mut sections = receiver.into_iter().collect::<Vec<_>>();
+        sections.sort_unstable_by_key(|&(idx, _)| idx);
+        let mut parts = sections
+            .into_iter()
+            .map(|(_, entries)| entries)
+            .collect::<io::Result<Vec<_>>>()?;
+        if !direct {
+            let mut buckets = (0..shard_amount).map(|_| vec![]).collect::<Vec<_>>();
+            for (hash, k, v) in parts.into_iter().flatten() {
+                buckets[shard_index(hash, shard_amount)].push((hash, k, v));
+            }
+            parts = buckets;
+        }
+
+        let shards = parts
+            .into_par_iter()
+            .map(|entries| {
+                let mut shard =
+                    HashMap::with_capacity_and_hasher(entries.len(), hash_builder.clone());
+                for (hash, k, v) in entries {
+                    insert_hashed(&mut shard, hash, k, v);
+                }
+                shard
+            })
+            .collect();
+
+        Ok(Self {
+            shards,
+            hash_builder,
+            poison_policy: PoisonPolicy::default(),
+            _phantom_data: PhantomData,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MutableShardMap, ShardMap};
+    use ::rayon::prelude::*;
+
+    #[test]
+    fn test_rayon_bulk_load() {
+        const N: usize = 100_000;
+        let map = (0..N)
+            .into_par_iter()
+            .map(|i| (i, i * 2))
+            .collect::<ShardMap<usize, usize>>();
+        assert_eq!(map.len(), N);
+        for i in 0..N {
+            assert_eq!(map.get(&i), Some(&(i * 2)));
+        }
+
+        let mut map = MutableShardMap::<usize, usize>::new();
+        map.par_extend((0..N).into_par_iter().map(|i| (i, i)));
+        (&map).par_extend((N..2 * N).into_par_iter().map(|i| (i, i)));
+        assert_eq!(map.len(), 2 * N);
+        assert_eq!(map.get_cloned(&(2 * N - 1)), Some(2 * N - 1));
+
+        let map = (0..N)
+            .into_par_iter()
+            .map(|i| (i, i))
+            .collect::<MutableShardMap<usize, usize>>();
+        assert_eq!(map.len(), N);
+    }
+
+    #[test]
+    fn test_rayon_iter() {
+        const N: usize = 100_000;
+        let map = (0..N)
+            .map(|i| (i, i * 2))
+            .collect::<ShardMap<usize, usize>>();
+
+        assert_eq!(map.par_iter().count(), N);
+        assert!(map.par_iter().all(|(k, v)| *v == k * 2));
+        assert_eq!(map.par_keys().sum::<usize>(), N * (N - 1) / 2);
+        assert_eq!(map.par_values().sum::<usize>(), N * (N - 1));
+        assert_eq!((&map).into_par_iter().count(), N);
+
+        let mut items = map.into_par_iter().collect::<Vec<_>>();
+        items.par_sort();
+        assert_eq!(items, (0..N).map(|i| (i, i * 2)).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{HashMap, InnerMap, MutableInnerMap, ShardMap};
+use ::serde::de::{DeserializeSeed, MapAccess, Visitor};
+use ::serde::ser::{Error as _, SerializeMap};
+use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::hash::{BuildHasher, Hash};
+use std::sync::{Mutex, RwLock};
+use std::{fmt, iter};
+
+impl<K, V, S> Serialize for ShardMap<K, V, S>
+where
+    K: Serialize,
+    V: Serialize,
+    S: BuildHasher + Clone,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (k, v) in self {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+// every shard is read-locked up front so the output is a point-in-time view
+// and its length is known before the first entry is written, which formats
+// such as bincode require.
+fn serialize_locked<K, V, S, T, Ser>(
+    map: &ShardMap<K, V, S, T>,
+    serializer: Ser,
+) -> Result<Ser::Ok, Ser::Error>
+where
+    K: Serialize,
+    V: Serialize,
+    T: MutableInnerMap<K, V, S>,
+    Ser: Serializer,
+{
+    let guards = map
+        .shards
+        .iter()
+        .map(|s| s.read_with(map.poison_policy))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(Ser::Error::custom)?;
+    let len = guards.iter().map(|m| m.len()).sum();
+    let mut state = serializer.serialize_map(Some(len))?;
+    for (k, v) in guards.iter().flat_map(|m| m.iter()) {
+        state.serialize_entry(k, v)?;
+    }
+    state.end()
+}
+
+impl<K, V, S> Serialize for ShardMap<K, V, S, Mutex<HashMap<K, V, S>>>
+where
+    K: Serialize,
+    V: Serialize,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_locked(self, serializer)
+    }
+}
+
+impl<K, V, S> Serialize for ShardMap<K, V, S, RwLock<HashMap<K, V, S>>>
+where
+    K: Serialize,
+    V: Serialize,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serialize_locked(self, serializer)
+    }
+}
+
+struct ShardMapVisitor<K, V, S, T: InnerMap<K, V, S>> {
+    map: ShardMap<K, V, S, T>,
+}
+
+impl<'de, K, V, S, T> Visitor<'de> for ShardMapVisitor<K, V, S, T>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Clone,
+    T: InnerMap<K, V, S>,
+{
+    type Value = ShardMap<K, V, S, T>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut map = self.map;
+        let mut error = None;
+        map.extend_shards(iter::from_fn(|| {
+            access.next_entry().unwrap_or_else(|e| {
+                error = Some(e);
+                None
+            })
+        }));
+        match error {
+            Some(e) => Err(e),
+            None => Ok(map),
+        }
+    }
+}
+
+impl<'de, K, V, S, T> Deserialize<'de> for ShardMap<K, V, S, T>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Clone + Default,
+    T: InnerMap<K, V, S>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let map = ShardMap::with_hasher(S::default());
+        deserializer.deserialize_map(ShardMapVisitor { map })
+    }
+}
+
+// deserializing with a map as the seed keeps its shard amount, capacity and
+// hasher, and adds the entries to it.
+impl<'de, K, V, S, T> DeserializeSeed<'de> for ShardMap<K, V, S, T>
+where
+    K: Deserialize<'de> + Eq + Hash,
+    V: Deserialize<'de>,
+    S: BuildHasher + Clone,
+    T: InnerMap<K, V, S>,
+{
+    type Value = Self;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ShardMapVisitor { map: self })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MutableShardMap, RwShardMap, ShardMap, SortedShard};
+    use ::serde::de::DeserializeSeed;
+    use std::hash::RandomState;
+
+    #[test]
+    fn test_serde() {
+        const N: usize = 1000;
+        let map = (0..N)
+            .map(|i| (i.to_string(), i))
+            .collect::<MutableShardMap<String, usize>>();
+        let json = serde_json::to_string(&map).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value.as_object().unwrap().len(), N);
+        assert_eq!(value["7"], 7);
+        let consistent = serde_json::to_string(&map.snapshot_consistent()).unwrap();
+        assert_eq!(consistent.len(), json.len());
+
+        let frozen: ShardMap<String, usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.get("7"), Some(&7));
+        assert_eq!(serde_json::to_string(&frozen).unwrap().len(), json.len());
+
+        let map: RwShardMap<String, usize> = serde_json::from_str(&json).unwrap();
+        assert_eq!(map.len(), N);
+        assert_eq!(serde_json::to_string(&map).unwrap().len(), json.len());
+
+        let seed = MutableShardMap::<String, usize>::with_capacity_and_shard_amount(N, 3);
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let map = seed.deserialize(&mut deserializer).unwrap();
+        assert_eq!(map.shard_amount(), 3);
+        assert_eq!(map.len(), N);
+        assert_eq!(map.get_cloned("7"), Some(7));
+
+        assert!(serde_json::from_str::<ShardMap<String, usize>>("[1]").is_err());
+
+        type Sorted = ShardMap<String, usize, RandomState, SortedShard<String, usize>>;
+        let sorted: Sorted = serde_json::from_str(&json).unwrap();
+        assert_eq!(sorted.len(), N);
+        assert_eq!(sorted.get("7"), Some(&7));
+        assert!(serde_json::from_str::<Sorted>(r#"{"a":1,"b":"x"}"#).is_err());
+
+        // bincode writes the map length before the entries.
+        let bytes = bincode::serialize(&map).unwrap();
+        let map: RwShardMap<String, usize> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(map.len(), N);
+        assert_eq!(bincode::serialize(&map).unwrap().len(), bytes.len());
+        let frozen: ShardMap<String, usize> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(frozen.get("7"), Some(&7));
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{HashMap, ImmutableInnerMap, InnerMap, MutableShardMap, ShardMap};
+use std::borrow::Borrow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::hash::{BuildHasher, Hash};
+use std::ops::{Bound, RangeBounds};
+use std::{mem, slice, vec};
+
+/// An immutable shard kept as a `Vec` of entries sorted by key.
+///
+/// A side index of `(hash, position)` pairs sorted by hash answers the hashed
+/// lookups of [`ImmutableInnerMap`] with a binary search, while the entries
+/// themselves serve ordered iteration and [`SortedShard::range`]. Inserting
+/// a single entry shifts both arrays, so shards are meant to be built in one
+/// go, by [`MutableShardMap::freeze_sorted`] or a bulk load such as `collect`
+/// or `extend`.
+pub struct SortedShard<K, V> {
+    entries: Vec<(K, V)>,
+    index: Vec<(u64, usize)>,
+}
+
+fn entry_ref<K, V>(entry: &(K, V)) -> (&K, &V) {
+    (&entry.0, &entry.1)
+}
+
+type EntryIter<'a, K, V> =
+    std::iter::Map<slice::Iter<'a, (K, V)>, fn(&'a (K, V)) -> (&'a K, &'a V)>;
+
+impl<K, V> SortedShard<K, V> {
+    fn find<Q>(&self, hash: u64, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let start = self.index.partition_point(|&(h, _)| h < hash);
+        self.index[start..]
+            .iter()
+            .take_while(|&&(h, _)| h == hash)
+            .map(|&(_, pos)| &self.entries[pos])
+            .find(|(key, _)| key.borrow() == k)
+            .map(|(_, v)| v)
+    }
+
+    fn range_slice<Q, R>(&self, range: &R) -> &[(K, V)]
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(q) => self.entries.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Excluded(q) => self.entries.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(q) => self.entries.partition_point(|(k, _)| k.borrow() <= q),
+            Bound::Excluded(q) => self.entries.partition_point(|(k, _)| k.borrow() < q),
+            Bound::Unbounded => self.entries.len(),
+        };
+        &self.entries[start..end.max(start)]
+    }
+
+    pub fn range<Q, R>(&self, range: R) -> EntryIter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        self.range_slice(&range).iter().map(entry_ref as _)
+    }
+}
+
+impl<K: Ord, V> SortedShard<K, V> {
+    // expects entries sorted by key without duplicates.
+    fn build(sorted: Vec<(u64, K, V)>) -> Self {
+        let mut index = sorted
+            .iter()
+            .enumerate()
+            .map(|(pos, &(hash, _, _))| (hash, pos))
+            .collect::<Vec<_>>();
+        index.sort_unstable();
+        let entries = sorted.into_iter().map(|(_, k, v)| (k, v)).collect();
+        Self { entries, index }
+    }
+}
+
+impl<K: Ord + Hash, V, S: BuildHasher + Clone> From<HashMap<K, V, S>> for SortedShard<K, V> {
+    fn from(map: HashMap<K, V, S>) -> Self {
+        let hash_builder = map.hasher().clone();
+        let mut entries = map
+            .into_iter()
+            .map(|(k, v)| (hash_builder.hash_one(&k), k, v))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by(|(_, a, _), (_, b, _)| a.cmp(b));
+        Self::build(entries)
+    }
+}
+
+impl<K: Ord, V, S> InnerMap<K, V, S> for SortedShard<K, V> {
+    fn with_capacity_and_hasher(capacity: usize, _: S) -> Self {
+        Self {
+            entries: Vec::with_capacity(capacity),
+            index: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn contains_key<Q>(&self, hash: u64, k: &Q) -> bool
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(hash, k).is_some()
+    }
+
+    fn insert_mut(&mut self, hash: u64, k: K, v: V) -> Option<V>
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        match self.entries.binary_search_by(|(key, _)| key.cmp(&k)) {
+            Ok(pos) => Some(mem::replace(&mut self.entries[pos].1, v)),
+            Err(pos) => {
+                self.entries.insert(pos, (k, v));
+                self.index
+                    .iter_mut()
+                    .filter(|(_, p)| *p >= pos)
+                    .for_each(|(_, p)| *p += 1);
+                let at = self.index.partition_point(|&(h, _)| h < hash);
+                self.index.insert(at, (hash, pos));
+                None
+            }
+        }
+    }
+
+    const BULK_BUILD: bool = true;
+
+    // merges everything with one stable sort instead of shifting the arrays
+    // for every entry.
+    fn extend_mut(&mut self, entries: impl IntoIterator<Item = (u64, K, V)>)
+    where
+        K: Eq + Hash,
+        S: BuildHasher,
+    {
+        let mut hashes = vec![0; self.entries.len()];
+        for &(hash, pos) in &self.index {
+            hashes[pos] = hash;
+        }
+        let mut merged = mem::take(&mut self.entries)
+            .into_iter()
+            .zip(hashes)
+            .map(|((k, v), hash)| (hash, k, v))
+            .chain(entries)
+            .collect::<Vec<_>>();
+        merged.sort_by(|(_, a, _), (_, b, _)| a.cmp(b));
+        merged.dedup_by(|later, kept| {
+            let duplicate = later.1 == kept.1;
+            if duplicate {
+                mem::swap(later, kept);
+            }
+            duplicate
+        });
+        *self = Self::build(merged);
+    }
+}
+
+impl<K: Ord, V, S> ImmutableInnerMap<K, V, S> for SortedShard<K, V> {
+    type Iter<'a>
+        = EntryIter<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn iter(&self) -> Self::Iter<'_> {
+        self.entries.iter().map(entry_ref as _)
+    }
+
+    fn get<Q>(&self, hash: u64, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Eq + Hash,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.find(hash, k)
+    }
+}
+
+impl<K, V> IntoIterator for SortedShard<K, V> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+struct Head<'a, K, V> {
+    entry: &'a (K, V),
+    shard: usize,
+}
+
+impl<K: Ord, V> PartialEq for Head<'_, K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.entry.0 == other.entry.0
+    }
+}
+
+impl<K: Ord, V> Eq for Head<'_, K, V> {}
+
+impl<K: Ord, V> PartialOrd for Head<'_, K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for Head<'_, K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entry.0.cmp(&other.entry.0)
+    }
+}
+
+/// Entries of every [`SortedShard`] of a map, merged in key order.
+pub struct SortedIter<'a, K, V> {
+    shards: Vec<slice::Iter<'a, (K, V)>>,
+    heads: BinaryHeap<Reverse<Head<'a, K, V>>>,
+}
+
+impl<'a, K: Ord, V> SortedIter<'a, K, V> {
+    fn new(mut shards: Vec<slice::Iter<'a, (K, V)>>) -> Self {
+        let heads = shards
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(shard, it)| {
+                Some(Reverse(Head {
+                    entry: it.next()?,
+                    shard,
+                }))
+            })
+            .collect();
+        Self { shards, heads }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for SortedIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let Reverse(head) = self.heads.pop()?;
+        if let Some(entry) = self.shards[head.shard].next() {
+            self.heads.push(Reverse(Head {
+                entry,
+                shard: head.shard,
+            }));
+        }
+        Some(entry_ref(head.entry))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.heads.len() + self.shards.iter().map(|it| it.len()).sum::<usize>();
+        (len, Some(len))
+    }
+}
+
+impl<K: Ord, V> ExactSizeIterator for SortedIter<'_, K, V> {}
+
+impl<K: Ord, V, S: BuildHasher + Clone> ShardMap<K, V, S, SortedShard<K, V>> {
+    pub fn range<Q, R>(&self, range: R) -> SortedIter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        SortedIter::new(
+            self.shards
+                .iter()
+                .map(|shard| shard.range_slice(&range).iter())
+                .collect(),
+        )
+    }
+
+    pub fn iter_sorted(&self) -> SortedIter<'_, K, V> {
+        SortedIter::new(
+            self.shards
+                .iter()
+                .map(|shard| shard.entries.iter())
+                .collect(),
+        )
+    }
+}
+
+impl<K: Ord + Hash, V, S: BuildHasher + Clone> MutableShardMap<K, V, S> {
+    /// Freezes the map with every shard rebuilt as a [`SortedShard`].
+    pub fn freeze_sorted(self) -> ShardMap<K, V, S, SortedShard<K, V>> {
+        ShardMap::from(self).map_shards(SortedShard::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{MutableShardMap, ShardMap, SortedShard};
+    use std::hash::RandomState;
+    use std::ops::Bound;
+
+    #[test]
+    fn test_sorted_shard() {
+        const N: usize = 1 << 15;
+        let map = MutableShardMap::<usize, usize>::with_shard_amount(8);
+        for i in (0..N).rev() {
+            map.insert(i, i * 2);
+        }
+        let frozen = map.freeze_sorted();
+        assert_eq!(frozen.len(), N);
+        assert_eq!(frozen.shard_amount(), 8);
+        for i in 0..N {
+            assert_eq!(frozen.get(&i), Some(&(i * 2)));
+        }
+        assert!(frozen.contains_key(&7));
+        assert!(!frozen.contains_key(&N));
+        assert!(frozen.shards.iter().all(|s| s.entries.is_sorted()));
+
+        assert!(frozen
+            .iter_sorted()
+            .map(|(k, v)| (*k, *v))
+            .eq((0..N).map(|i| (i, i * 2))));
+        assert_eq!(frozen.iter_sorted().len(), N);
+        assert!(frozen.range(100..200).map(|(k, _)| *k).eq(100..200));
+        assert!(frozen.range(..=10).map(|(k, _)| *k).eq(0..=10));
+        assert!(frozen.range(N - 5..).map(|(k, _)| *k).eq(N - 5..N));
+        assert_eq!(frozen.range(N..).count(), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = frozen.range(20..10).count();
+        assert_eq!(empty, 0);
+
+        let mut entries = frozen.into_iter().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert!(entries.into_iter().eq((0..N).map(|i| (i, i * 2))));
+
+        let map = (0..1000)
+            .rev()
+            .map(|i| (format!("{i:04}"), i))
+            .chain([("0007".to_string(), 70)])
+            .collect::<ShardMap<String, usize, RandomState, SortedShard<_, _>>>();
+        assert_eq!(map.len(), 1000);
+        assert_eq!(map.get("0007"), Some(&70));
+        assert_eq!(map.get("0042"), Some(&42));
+        assert!(map
+            .iter_sorted()
+            .map(|(_, v)| *v)
+            .eq([0, 1, 2, 3, 4, 5, 6, 70].into_iter().chain(8..1000)));
+        assert!(map
+            .range::<str, _>((Bound::Included("0010"), Bound::Excluded("0020")))
+            .map(|(_, v)| *v)
+            .eq(10..20));
+    }
+
+    #[test]
+    fn test_sorted_bulk_load() {
+        // large enough that inserting entry by entry would take minutes.
+        const N: usize = 1_000_000;
+        let mut map =
+            (0..N)
+                .rev()
+                .map(|i| (i, i))
+                .collect::<ShardMap<usize, usize, RandomState, SortedShard<_, _>>>();
+        assert_eq!(map.len(), N);
+        assert!(map.iter_sorted().map(|(k, _)| *k).eq(0..N));
+
+        map.extend(
+            (N / 2..N + 10)
+                .map(|i| (i, i + 1))
+                .chain([(7, 70), (7, 71)]),
+        );
+        assert_eq!(map.len(), N + 10);
+        assert_eq!(map.get(&7), Some(&71));
+        assert_eq!(map.get(&(N / 2 - 1)), Some(&(N / 2 - 1)));
+        assert_eq!(map.get(&(N / 2)), Some(&(N / 2 + 1)));
+        assert_eq!(map.get(&(N + 9)), Some(&(N + 10)));
+        assert!(map.iter_sorted().map(|(k, _)| *k).eq(0..N + 10));
+
+        #[cfg(feature = "rayon")]
+        {
+            use ::rayon::prelude::*;
+            let map = (0..N).into_par_iter().map(|i| (i, i)).collect::<ShardMap<
+                usize,
+                usize,
+                RandomState,
+                SortedShard<_, _>,
+            >>();
+            assert!(map.iter_sorted().map(|(k, _)| *k).eq(0..N));
+        }
+    }
+}